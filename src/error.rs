@@ -0,0 +1,47 @@
+use std::io;
+use thiserror::Error;
+
+/// Everything that can go wrong while searching.
+#[derive(Debug, Error)]
+pub enum MyGrepError {
+    /// The pattern doesn't compile with the regex engine
+    #[error("pattern is not a valid regex: {0}")]
+    InvalidRegex(#[from] regex::Error),
+    /// The pattern doesn't compile with the fancy-regex engine, or a match failed
+    #[error("pcre error: {0}")]
+    Pcre(#[from] fancy_regex::Error),
+    /// The pattern is not a `LOW-HIGH` range of numbers
+    #[error("pattern is not a valid numeric range, expected LOW-HIGH like 500-599: {0}")]
+    InvalidRange(String),
+    /// Two options of a builder that can't be used together
+    #[error("invalid search options: {0}")]
+    InvalidOptions(String),
+    /// The input is not valid UTF-8
+    #[error("input is not valid UTF-8")]
+    Encoding,
+    /// Reading the input failed
+    #[error(transparent)]
+    Io(io::Error),
+}
+
+impl MyGrepError {
+    /// The exit code documented in the help: 2 for an invalid pattern, 1 for the rest.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MyGrepError::InvalidRegex(_) | MyGrepError::InvalidRange(_) => 2,
+            MyGrepError::Pcre(fancy_regex::Error::RuntimeError(_)) => 1,
+            MyGrepError::Pcre(_) => 2,
+            MyGrepError::InvalidOptions(_) | MyGrepError::Encoding | MyGrepError::Io(_) => 1,
+        }
+    }
+}
+
+impl From<io::Error> for MyGrepError {
+    fn from(error: io::Error) -> Self {
+        // lines() reports invalid UTF-8 as InvalidData
+        if error.kind() == io::ErrorKind::InvalidData {
+            return MyGrepError::Encoding;
+        }
+        MyGrepError::Io(error)
+    }
+}
@@ -0,0 +1,618 @@
+use regex::{Regex, RegexBuilder};
+use std::io::BufRead;
+
+pub mod error;
+
+pub use error::MyGrepError;
+
+/// A (start,end) byte range of the pattern inside a line.
+///
+/// It is empty (`start == end`) for zero-width regex matches like `^`, `$` or `\b`.
+pub type Span = (usize, usize);
+
+/// What to search for and how.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// The pattern to look for
+    pub pattern: String,
+    /// Pattern is a Regex
+    pub regex: bool,
+    /// Case Insensitive
+    pub insensitive: bool,
+    /// Use the fancy-regex engine (lookaround and backreferences), implies regex
+    pub pcre: bool,
+    /// Find overlapping occurrences of a literal pattern
+    pub overlapping: bool,
+    /// Match only whole words
+    pub word: bool,
+    /// Only ASCII letters, digits and `_` are word characters (with word)
+    pub word_ascii: bool,
+    /// The pattern is a `LOW-HIGH` range, matching the numbers in it
+    pub numeric_range: bool,
+    /// Match the text at most this many edits (Levenshtein) away from a literal pattern
+    pub fuzzy: Option<usize>,
+}
+
+impl SearchOptions {
+    /// Start building the options of a pattern, the other options are off.
+    pub fn builder(pattern: impl Into<String>) -> SearchOptionsBuilder {
+        SearchOptionsBuilder { opts: SearchOptions { pattern: pattern.into(), ..Default::default() } }
+    }
+}
+
+/// Builds [`SearchOptions`] one setter at a time, checking in [`build`](SearchOptionsBuilder::build)
+/// that they can go together, like the command line does.
+#[derive(Debug, Clone)]
+pub struct SearchOptionsBuilder {
+    opts: SearchOptions,
+}
+
+impl SearchOptionsBuilder {
+    /// The pattern is a Regex
+    pub fn regex(mut self, regex: bool) -> Self {
+        self.opts.regex = regex;
+        self
+    }
+
+    /// Case Insensitive
+    pub fn insensitive(mut self, insensitive: bool) -> Self {
+        self.opts.insensitive = insensitive;
+        self
+    }
+
+    /// Use the fancy-regex engine (lookaround and backreferences), implies regex
+    pub fn pcre(mut self, pcre: bool) -> Self {
+        self.opts.pcre = pcre;
+        self
+    }
+
+    /// Find overlapping occurrences of a literal pattern
+    pub fn overlapping(mut self, overlapping: bool) -> Self {
+        self.opts.overlapping = overlapping;
+        self
+    }
+
+    /// Match only whole words
+    pub fn word(mut self, word: bool) -> Self {
+        self.opts.word = word;
+        self
+    }
+
+    /// Only ASCII letters, digits and `_` are word characters (with word)
+    pub fn word_ascii(mut self, word_ascii: bool) -> Self {
+        self.opts.word_ascii = word_ascii;
+        self
+    }
+
+    /// The pattern is a `LOW-HIGH` range, matching the numbers in it
+    pub fn numeric_range(mut self, numeric_range: bool) -> Self {
+        self.opts.numeric_range = numeric_range;
+        self
+    }
+
+    /// Match the text at most `distance` edits (Levenshtein) away from a literal pattern
+    pub fn fuzzy(mut self, distance: usize) -> Self {
+        self.opts.fuzzy = Some(distance);
+        self
+    }
+
+    /// The options, or [`MyGrepError::InvalidOptions`] for two that can't be used together.
+    pub fn build(self) -> Result<SearchOptions, MyGrepError> {
+        let opts = self.opts;
+        let conflict = |first: &str, second: &str| Err(MyGrepError::InvalidOptions(format!("{} can't be used with {}", first, second)));
+        if opts.overlapping && (opts.regex || opts.pcre) {
+            return conflict("overlapping", "regex");
+        }
+        if opts.word_ascii && !opts.word {
+            return Err(MyGrepError::InvalidOptions("word_ascii needs word".to_string()));
+        }
+        if opts.numeric_range {
+            if opts.regex || opts.pcre {
+                return conflict("numeric_range", "regex");
+            }
+            if opts.overlapping {
+                return conflict("numeric_range", "overlapping");
+            }
+            if opts.word {
+                return conflict("numeric_range", "word");
+            }
+            parse_numeric_range(&opts.pattern)?;
+        }
+        if opts.fuzzy.is_some() {
+            if opts.regex || opts.pcre {
+                return conflict("fuzzy", "regex");
+            }
+            if opts.overlapping {
+                return conflict("fuzzy", "overlapping");
+            }
+            if opts.word {
+                return conflict("fuzzy", "word");
+            }
+            if opts.numeric_range {
+                return conflict("fuzzy", "numeric_range");
+            }
+        }
+        Ok(opts)
+    }
+}
+
+/// A line that contains the pattern.
+///
+/// `line_number` is 1-based, `spans` are the (start,end) byte ranges of the
+/// pattern inside `line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    pub line_number: usize,
+    pub line: String,
+    pub spans: Vec<Span>,
+}
+
+pub fn line_contains_pattern(line: &str, pattern: &str) -> bool {
+    // return all start indices where pattern is found
+    line.contains(pattern)
+}
+
+pub fn regex_match_line(line: &str, re: &Regex) -> bool {
+    re.is_match(line)
+}
+
+pub fn get_normal_indexes(line: &str, pattern: &str) -> Vec<(usize, usize)> {
+    // return a Vec of (start,end) indices where pattern is found
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    line.match_indices(pattern).for_each(|start| {
+        indexes.push((start.0, start.0 + pattern.len()));
+    });
+    indexes
+}
+
+pub fn get_overlapping_indexes(line: &str, pattern: &str) -> Vec<(usize, usize)> {
+    // like get_normal_indexes, but the search restarts one char after the
+    // start of the previous occurrence, so "aa" is found 3 times in "aaaa"
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    let mut from = 0;
+    while let Some(found) = line[from..].find(pattern) {
+        let start = from + found;
+        indexes.push((start, start + pattern.len()));
+        match line[start..].chars().next() {
+            Some(c) => from = start + c.len_utf8(),
+            None => break,
+        }
+    }
+    indexes
+}
+
+pub fn get_overlapping_caseless_indexes(line: &str, re: &Regex) -> Vec<(usize, usize)> {
+    // like get_overlapping_indexes, for a literal compiled as a case insensitive regex
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    let mut from = 0;
+    while let Some(found) = re.find_at(line, from) {
+        indexes.push((found.start(), found.end()));
+        match line[found.start()..].chars().next() {
+            Some(c) => from = found.start() + c.len_utf8(),
+            None => break,
+        }
+    }
+    indexes
+}
+
+pub fn get_grep_indexes(line: &str, re: &Regex) -> Vec<(usize, usize)>{
+    // return a Vec of (start,end) indices where regex pattern match
+
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    re.find_iter(line).for_each(|m| {
+        indexes.push((m.start(), m.end()));
+    });
+
+    indexes
+}
+
+pub fn fancy_regex_match_line(line: &str, re: &fancy_regex::Regex) -> Result<bool, MyGrepError> {
+    Ok(re.is_match(line)?)
+}
+
+pub fn get_fancy_indexes(line: &str, re: &fancy_regex::Regex) -> Vec<(usize, usize)> {
+    // same as get_grep_indexes but with the fancy-regex engine, a match that
+    // errors (e.g. backtrack limit reached) ends the search on this line
+
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    re.find_iter(line).map_while(Result::ok).for_each(|m| {
+        indexes.push((m.start(), m.end()));
+    });
+
+    indexes
+}
+
+/// Letters, digits and `_` are word characters, in any script unless `ascii`.
+pub fn is_word_char(c: char, ascii: bool) -> bool {
+    if ascii {
+        c.is_ascii_alphanumeric() || c == '_'
+    } else {
+        c.is_alphanumeric() || c == '_'
+    }
+}
+
+/// The span `start..end` of `line` is not glued to a word character on either side.
+pub fn is_whole_word(line: &str, start: usize, end: usize, ascii: bool) -> bool {
+    let before = line[..start].chars().next_back();
+    let after = line[end..].chars().next();
+    !before.is_some_and(|c| is_word_char(c, ascii)) && !after.is_some_and(|c| is_word_char(c, ascii))
+}
+
+/// Wrap a regex pattern in word boundaries, Unicode ones unless `ascii`.
+pub fn word_regex(regex_pattern: &str, ascii: bool) -> String {
+    if ascii {
+        format!(r"(?-u:\b)(?:{})(?-u:\b)", regex_pattern)
+    } else {
+        format!(r"\b(?:{})\b", regex_pattern)
+    }
+}
+
+/// Expand a replacement string for one regex match, like sed/perl.
+///
+/// `$1`, `${1}`, `$name` and `${name}` are the capture groups (`$$` is a `$`),
+/// `\U` and `\L` turn what follows to uppercase or lowercase until `\E` or
+/// the next `\U`/`\L`. Any other backslash is kept as it is.
+pub fn expand_replacement(caps: &regex::Captures, replacement: &str) -> String {
+    #[derive(Clone, Copy)]
+    enum Case {
+        Keep,
+        Upper,
+        Lower,
+    }
+
+    let mut expanded = String::new();
+    let mut case = Case::Keep;
+    let mut rest = replacement;
+    loop {
+        // the text up to the next case escape is expanded with the current case
+        let next = ["\\U", "\\L", "\\E"].iter()
+            .filter_map(|escape| rest.find(escape))
+            .min();
+        let (part, after) = match next {
+            Some(at) => (&rest[..at], Some(&rest[at..])),
+            None => (rest, None),
+        };
+        let mut group = String::new();
+        caps.expand(part, &mut group);
+        match case {
+            Case::Keep => expanded.push_str(&group),
+            Case::Upper => expanded.push_str(&group.to_uppercase()),
+            Case::Lower => expanded.push_str(&group.to_lowercase()),
+        }
+        let Some(after) = after else {
+            break;
+        };
+        case = match &after[..2] {
+            "\\U" => Case::Upper,
+            "\\L" => Case::Lower,
+            _ => Case::Keep,
+        };
+        rest = &after[2..];
+    }
+    expanded
+}
+
+/// The pattern has an uppercase letter that it matches literally, for smart case.
+///
+/// In a regex the escapes are skipped, so `\\W+` or `\\p{Lu}` are still all lowercase.
+pub fn has_uppercase_literal(pattern: &str, regex: bool) -> bool {
+    if !regex {
+        return pattern.chars().any(char::is_uppercase);
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            // \p{Greek} and \P{Lu}, the name of the class is not a literal either
+            if let Some('p' | 'P') = chars.next() {
+                if chars.clone().next() == Some('{') {
+                    chars.by_ref().find(|&c| c == '}');
+                } else {
+                    chars.next();
+                }
+            }
+            continue;
+        }
+        if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+/// Parse a `LOW-HIGH` numeric range, the brackets of `[500-599]` are optional.
+pub fn parse_numeric_range(pattern: &str) -> Result<(u64, u64), MyGrepError> {
+    let invalid = || MyGrepError::InvalidRange(pattern.to_string());
+    let range = pattern.strip_prefix('[').and_then(|range| range.strip_suffix(']')).unwrap_or(pattern);
+    let (low, high) = range.split_once('-').ok_or_else(invalid)?;
+    let low: u64 = low.trim().parse().map_err(|_| invalid())?;
+    let high: u64 = high.trim().parse().map_err(|_| invalid())?;
+    if low > high {
+        return Err(invalid());
+    }
+    Ok((low, high))
+}
+
+pub fn get_numeric_indexes(line: &str, low: u64, high: u64) -> Vec<(usize, usize)> {
+    // the (start,end) indices of the runs of ASCII digits whose number is in low..=high,
+    //   a run too long for a u64 is never in the range
+    let mut indexes : Vec<(usize,usize)> = Vec::new();
+    let bytes = line.as_bytes();
+    let mut start = 0;
+    while start < bytes.len() {
+        if !bytes[start].is_ascii_digit() {
+            start += 1;
+            continue;
+        }
+        let end = start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if line[start..end].parse::<u64>().is_ok_and(|number| low <= number && number <= high) {
+            indexes.push((start, end));
+        }
+        start = end;
+    }
+    indexes
+}
+
+/// Union the overlapping spans, so each byte of a line is styled at most once.
+///
+/// The spans can come in any order and from different patterns, like `foo` and
+/// `oba` on `foobar` that become a single `fooba`. Touching spans (`end == start`)
+/// are different matches and are kept apart, empty spans are dropped.
+pub fn merge_spans(spans: &[Span]) -> Vec<Span> {
+    let mut sorted: Vec<Span> = spans.iter().copied().filter(|&(start, end)| start < end).collect();
+    sorted.sort_unstable();
+    let mut merged: Vec<Span> = Vec::with_capacity(sorted.len());
+    for (start, end) in sorted {
+        match merged.last_mut() {
+            Some(last) if start < last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// The span of `line` closest to `pattern`, if it is at most `max_distance` edits away.
+///
+/// Any substring can match, so `connection` is found in `conection refused` with 1.
+/// The fewest edits win, then the leftmost end; an empty span is never a match.
+/// With `insensitive` the chars are compared lowercased, the pattern is expected lowercase.
+pub fn get_fuzzy_span(line: &str, pattern: &[char], max_distance: usize, insensitive: bool) -> Option<Span> {
+    // Sellers' algorithm, an edit distance where the match can start anywhere in the line:
+    //   cost[i] is the distance of pattern[..i] to the best text ending at the current char,
+    //   start[i] the byte where that text starts
+    let mut cost: Vec<usize> = (0..=pattern.len()).collect();
+    let mut start: Vec<usize> = vec![0; pattern.len() + 1];
+    let mut best: Option<(usize, Span)> = None;
+    for (byte, c) in line.char_indices() {
+        let end = byte + c.len_utf8();
+        let c = if insensitive { c.to_lowercase().next().unwrap_or(c) } else { c };
+        let (mut diagonal, mut diagonal_start) = (cost[0], start[0]);
+        cost[0] = 0;
+        start[0] = end;
+        for i in 1..=pattern.len() {
+            let substitution = diagonal + usize::from(pattern[i - 1] != c);
+            let (above, above_start) = (cost[i], start[i]);
+            let (value, from) = if substitution <= above + 1 && substitution <= cost[i - 1] + 1 {
+                (substitution, diagonal_start)
+            } else if above <= cost[i - 1] {
+                (above + 1, above_start)
+            } else {
+                (cost[i - 1] + 1, start[i - 1])
+            };
+            (diagonal, diagonal_start) = (above, above_start);
+            cost[i] = value;
+            start[i] = from;
+        }
+        let distance = cost[pattern.len()];
+        let span = (start[pattern.len()], end);
+        if distance <= max_distance && span.0 < span.1 && best.is_none_or(|(best_distance, _)| distance < best_distance) {
+            best = Some((distance, span));
+        }
+    }
+    best.map(|(_, span)| span)
+}
+
+/// How a pattern is looked for, compiled once.
+#[derive(Debug, Clone)]
+enum Engine {
+    Literal(String),
+    // a literal searched case insensitively
+    Caseless(Regex),
+    Range(u64, u64),
+    Fuzzy(Vec<char>, usize),
+    Regex(Regex),
+    Fancy(fancy_regex::Regex),
+}
+
+/// The compiled form of [`SearchOptions`], built once and used for every line.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    opts: SearchOptions,
+    engine: Engine,
+}
+
+impl Matcher {
+    /// Compile the pattern, an invalid regex is reported here instead of on the first line.
+    pub fn new(opts: &SearchOptions) -> Result<Matcher, MyGrepError> {
+        let mut pattern = opts.pattern.clone();
+        if opts.word && (opts.regex || opts.pcre) {
+            pattern = word_regex(&pattern, opts.word_ascii);
+        }
+
+        // a regex is made case insensitive with its own flag, lowercasing `\W` or `\S`
+        //   would change what it means; a literal too, as an escaped regex: lowercasing the line
+        //   can change the length of a char (K, İ, ẞ), the spans must be in the line itself
+        let engine = if opts.numeric_range {
+            let (low, high) = parse_numeric_range(&opts.pattern)?;
+            Engine::Range(low, high)
+        } else if let Some(distance) = opts.fuzzy {
+            let pattern = if opts.insensitive { pattern.to_lowercase() } else { pattern };
+            Engine::Fuzzy(pattern.chars().collect(), distance)
+        } else if opts.pcre {
+            if opts.insensitive {
+                pattern = format!("(?i){}", pattern);
+            }
+            Engine::Fancy(fancy_regex::Regex::new(&pattern)?)
+        } else if opts.regex {
+            Engine::Regex(RegexBuilder::new(&pattern).case_insensitive(opts.insensitive).build()?)
+        } else if opts.insensitive {
+            Engine::Caseless(RegexBuilder::new(&regex::escape(&pattern)).case_insensitive(true).build()?)
+        } else {
+            Engine::Literal(pattern)
+        };
+        Ok(Matcher { opts: opts.clone(), engine })
+    }
+
+    /// The options the matcher was built from.
+    pub fn options(&self) -> &SearchOptions {
+        &self.opts
+    }
+
+    /// Check a single line without collecting the spans.
+    ///
+    /// It allocates nothing, unless the pattern is a literal searched as a whole word.
+    pub fn is_match(&self, line: &str) -> Result<bool, MyGrepError> {
+        match &self.engine {
+            Engine::Fancy(re) => fancy_regex_match_line(line, re),
+            Engine::Regex(re) => Ok(regex_match_line(line, re)),
+            Engine::Range(..) | Engine::Fuzzy(..) => Ok(self.match_line(line)?.is_some()),
+            Engine::Literal(_) | Engine::Caseless(_) if self.opts.word || self.opts.overlapping => Ok(self.match_line(line)?.is_some()),
+            Engine::Caseless(re) => Ok(regex_match_line(line, re)),
+            Engine::Literal(pattern) => Ok(line_contains_pattern(line, pattern)),
+        }
+    }
+
+    /// Check a single line, returning the spans of the pattern if it is found.
+    pub fn match_line(&self, line: &str) -> Result<Option<Vec<Span>>, MyGrepError> {
+        let mut indexes = match &self.engine {
+            Engine::Fancy(re) => {
+                if !fancy_regex_match_line(line, re)? {
+                    return Ok(None);
+                }
+                return Ok(Some(get_fancy_indexes(line, re)));
+            }
+            Engine::Regex(re) => {
+                if !regex_match_line(line, re) {
+                    return Ok(None);
+                }
+                return Ok(Some(get_grep_indexes(line, re)));
+            }
+            Engine::Range(low, high) => {
+                let indexes = get_numeric_indexes(line, *low, *high);
+                return Ok(if indexes.is_empty() { None } else { Some(indexes) });
+            }
+            Engine::Fuzzy(pattern, distance) => {
+                return Ok(get_fuzzy_span(line, pattern, *distance, self.opts.insensitive).map(|span| vec![span]));
+            }
+            Engine::Caseless(re) => {
+                if !regex_match_line(line, re) {
+                    return Ok(None);
+                }
+                if self.opts.overlapping {
+                    get_overlapping_caseless_indexes(line, re)
+                } else {
+                    get_grep_indexes(line, re)
+                }
+            }
+            Engine::Literal(pattern) => {
+                if !line_contains_pattern(line, pattern) {
+                    return Ok(None);
+                }
+                if self.opts.overlapping {
+                    get_overlapping_indexes(line, pattern)
+                } else {
+                    get_normal_indexes(line, pattern)
+                }
+            }
+        };
+        if self.opts.word {
+            indexes.retain(|&(start, end)| is_whole_word(line, start, end, self.opts.word_ascii));
+            if indexes.is_empty() {
+                return Ok(None);
+            }
+        }
+        Ok(Some(indexes))
+    }
+}
+
+/// Search every line of `content` and collect the ones that match.
+pub fn search_lines(content: &str, opts: &SearchOptions) -> Result<Vec<Match>, MyGrepError> {
+    let mut found = vec![];
+    for_each_match(content, opts, |found_match| found.push(found_match.clone()))?;
+    Ok(found)
+}
+
+/// Search every line of `content` and hand each match to `on_match`, in order.
+///
+/// Nothing is printed or collected: the closure decides where a match goes
+/// (a `Vec`, a channel, a GUI...). The number of matches is returned. The
+/// pattern is compiled before the first line, an invalid one calls `on_match`
+/// for no line at all; a fancy regex failing on a line stops the search there.
+/// The mygrep binary prints a plain search (one pattern, no other mode)
+/// through it; its own loop combines several patterns with the context and
+/// the other output modes.
+pub fn for_each_match<F>(content: &str, opts: &SearchOptions, mut on_match: F) -> Result<usize, MyGrepError>
+where
+    F: FnMut(&Match),
+{
+    let matcher = Matcher::new(opts)?;
+    let mut count = 0;
+    for (index, line) in content.lines().enumerate() {
+        if let Some(spans) = matcher.match_line(line)? {
+            on_match(&Match { line_number: index + 1, line: line.to_string(), spans });
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// The pattern is found in at least one line of `content`.
+///
+/// The search stops at the first line that matches. An invalid pattern, or a
+/// fancy regex that fails on a line, is never a match.
+pub fn any_match(content: &str, opts: &SearchOptions) -> bool {
+    let Ok(matcher) = Matcher::new(opts) else {
+        return false;
+    };
+    content.lines().any(|line| matcher.is_match(line).unwrap_or(false))
+}
+
+/// Lazily search `reader`, yielding matches as the lines are read.
+///
+/// Each line is read into a fresh `String` that is moved into the yielded
+/// [`Match`], so matches own their text and never borrow from the reader or
+/// from each other: they can be kept, sent elsewhere or dropped freely.
+/// Non matching lines are dropped as soon as they are checked, so memory use
+/// stays bounded by the longest line rather than by the size of the input.
+///
+/// The pattern is compiled once, when the iterator is created: an invalid
+/// regex is the first and only item. A read error is yielded as an `Err` and
+/// ends the iteration, invalid UTF-8 is reported as [`MyGrepError::Encoding`].
+pub fn matches<'a>(
+    reader: impl BufRead + 'a,
+    opts: &'a SearchOptions,
+) -> impl Iterator<Item = Result<Match, MyGrepError>> + 'a {
+    let (matcher, invalid) = match Matcher::new(opts) {
+        Ok(matcher) => (Some(matcher), None),
+        Err(error) => (None, Some(error)),
+    };
+    let mut failed = false;
+    let found = reader
+        .lines()
+        .enumerate()
+        .map_while(move |(index, line)| {
+            let matcher = matcher.as_ref()?;
+            if failed {
+                return None;
+            }
+            let found = line
+                .map_err(MyGrepError::from)
+                .and_then(|line| {
+                    Ok(matcher.match_line(&line)?
+                        .map(|spans| Match { line_number: index + 1, line, spans }))
+                });
+            failed = found.is_err();
+            Some(found.transpose())
+        })
+        .flatten();
+    invalid.map(Err).into_iter().chain(found)
+}
@@ -1,14 +1,20 @@
 use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use clap::{parser::ValueSource, ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use colored::*;
-use std::io::{self, BufRead};
-use regex::Regex;
-use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use mygrep::{expand_replacement, has_uppercase_literal, merge_spans, word_regex, Matcher, MyGrepError, SearchOptions, Span};
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::{process, vec};
 
 
 /// Search for a pattern in a file and display the lines that contain it.
 #[derive(Parser, Debug, Clone)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 #[command(name = "MyGrep")]
 #[command(author = "Riccardo Bella <raikoug@gmail.com>")]
 #[command(version = "1.1.0")]
@@ -18,28 +24,136 @@ use std::{process, vec};
     "A regex like implementation for windows.\n\
     \n\
     The pattern is a string to search for in the file.\n\
+    More patterns can be given with -e (repeated), then the first positional is the path.\n\
+    The patterns_env option is used to read more patterns from an environment variable, one per line.\n\
     The path is the file to search in.\n\
+    The command option is used to search the output of a shell command, never build it from untrusted input.\n\
+    The archive option is used to search the text members of a .zip, named archive.zip:member.\n\
+    The path_contains option is used to search only the files whose path contains a text, like src/.\n\
+    The files_from option is used to search the files listed in a file (- for stdin), files_from0 with NUL separators.\n\
+    The interactive option is used to read a file once and search it for every pattern typed on stdin.\n\
     If no path is given, the program will read from stdin.\n\
     \n\
+    The all option is used to print only the lines containing all the patterns given with -e, instead of any of them.\n\
+    The and_not option is used to reject the matching lines that also contain this pattern (repeatable).\n\
+    The lines option is used to search only a range of lines, like 100:200, 100: or :200.\n\
+    The since and until options are used to search only the lines whose timestamp is in the window.\n\
+    The json_field option is used to search only the value at a dotted path of each JSON line, like request.status.\n\
+    The diff option is used to search only the added lines of a unified diff, `git diff | mygrep --diff TODO`.\n\
+    The join_lines option is used to glue every N lines into one before searching, for records wrapped over N lines.\n\
+    The near option is used to print the lines between two patterns of -e found at most N lines apart.\n\
     The color is the color to use for the pattern found.\n\
+    The color_mode is used to choose when to use colors: auto (only when printing to a terminal), always or never.\n\
+    The output option is used to write the results to a file instead of stdout, colored only with color_mode always.\n\
+    The html option is used to print the matching lines as a standalone HTML page, with their highlighting.\n\
+    The wrap option is used to break the printed lines at the width of the terminal, keeping the colors.\n\
+    The snippet option is used to print only RADIUS chars around each match of a very long line.\n\
+    The max_output_bytes option is used to stop printing before the output goes past N bytes.\n\
+    The limit_memory option is used to read a file bigger than SIZE line by line, like stdin.\n\
+    The max_line_length option is used to skip the lines longer than N bytes before any matching.\n\
+    The line_buffered option is used to flush the output after every line, for the next program of a pipeline.\n\
+    Stdin and the named pipes are searched line by line as they are read, like `tail -f app.log | mygrep error`.\n\
     The bold, underline, italic and strike options are used to format the pattern found.\n\
+    The color_line option is used to tint the whole matching line, with a color different from the pattern one.\n\
+    The line_number_color and filename_color options are used to style the line numbers and the file names.\n\
+    The MYGREP_COLORS environment variable is used to reuse a GREP_COLORS setting, like ms=01;31:fn=35:ln=32.\n\
+    The first_match_only option is used to color only the first match of each line.\n\
+    The max_matches_per_line option is used to color only the first N matches of each line.\n\
+    The min_matches_per_line option is used to select only the lines where the patterns are found at least N times.\n\
+    The rainbow option is used to color the matches of a line in turn with six colors, to tell them apart.\n\
     The line_numbers option is used to show the line number of the pattern found.\n\
+    The no_line_numbers, no_heading, no_bold, no_insensitive, no_smart_case and no_section options are used to turn them off again.\n\
+    The column option is used to print the 1-based byte column of the first match after the line number.\n\
+    The column_visual option is used to print the column as seen in an editor, a tab counts as tabs_c columns.\n\
+    The heading option is used to print the file name once above its matches.\n\
+    The record_delimiter option is used to split the input on a string instead of the lines, like \\x1e.\n\
+    The null option is used to end the printed file names with a NUL byte, e.g. for xargs -0.\n\
+    The pretty option is used to turn on heading and line_numbers and to keep the colors when piped.\n\
+    The ascii option is used to print no escape codes at all and to draw the gutter with |.\n\
+    The gutter option is used to draw a separator between the line number and the line, like `7 │ foo`.\n\
+    The context_indent option is used to mark the context lines with `-` and the matching lines with `:`.\n\
+    The field_separator (or output_separator) option is used to print this separator in place of `: ` between the fields.\n\
     The regex option is used to search for a regex pattern.\n\
-    The insensitive option is used to search for a case insensitive pattern.\n\
+    The pcre option is used to search for a regex pattern with lookaround and backreferences (slower, implies regex).\n\
+    The regex_engine option is used to pick the engine of a regex: default (linear time) or fancy (the same as pcre).\n\
+    A regex that matches an empty string (like ^, $ or \\b) selects the line, with nothing to highlight.\n\
+    The word option is used to match only whole words, letters digits and _ of any script are word characters.\n\
+    The word_ascii option is used to consider only ASCII letters, digits and _ as word characters (requires word).\n\
+    The insensitive option is used to search for a case insensitive pattern, a regex uses its case insensitive flag.\n\
+    The smart_case option is used to search case insensitively unless the pattern contains an uppercase letter.\n\
+    The interpret_escapes option is used to turn \\t, \\n, \\xNN... of a literal pattern into their chars.\n\
+    The numeric_range option is used to look for the numbers in a range instead of a text, like 500-599.\n\
+    The fuzzy option is used to find a literal pattern with at most N typos, much slower than the exact search.\n\
+    The invert option is used to select the lines that don't contain the pattern.\n\
+    The overlapping option is used to find the overlapping occurrences of a literal pattern (`aa` 3 times in `aaaa`).\n\
     The after option is used to print the number of lines after the match (not compatible with section).\n\
     The before option is used to print the number of lines before the match (not compatible with section).\n\
-    The section option is used to print the section (same indentation or more) of the file where the pattern is found. (Not compatible with after and or before)\n\
+    The context option is used to print the same number of lines before and after the match.\n\
+    The dedupe_context option is used to print the context shared by close matches once, as a single block.\n\
+    The window option is used to give the context before and after in one token, like 2:5.\n\
+    The text option is used to search the inputs that are not UTF-8, the invalid bytes are read as �.\n\
+    The keep_bom option is used to keep the UTF-8 BOM at the start of the inputs, it is stripped by default.\n\
+    The strip_ansi option is used to remove the ANSI escape codes of the input before searching it.\n\
+    The byte_offset option is used to print the 0-based byte offset of each printed line after the line number.\n\
+    The replace option is used to print the matching lines with the matches replaced, the file is not changed.\n\
+    The output_dir option is used to write the replaced files under DIR at the same relative paths instead.\n\
+    The extract option is used to print the named groups of each match of a regex, tab separated.\n\
+    The only_matching option is used to print only the matched parts of the line, one per line.\n\
+    The join option is used to print the matches of a line on one line, joined by the separator (requires only_matching).\n\
+    The pre and post options are used to bracket each matching line, like `--pre '<mark>' --post '</mark>'`.\n\
+    The last option is used to print only the last matching line of a file, with its context.\n\
+    The sort_matches option is used to print the found patterns of all the searched files sorted.\n\
+    The count_by option is used to count the matching lines by the first group of a regex, like the status codes.\n\
+    The count option is used to print only the number of matching lines, 0 included for a single input.\n\
+    The include_zero option is used with count over several files to print the ones without a match too.\n\
+    The min_count option is used to leave out the files with fewer than N matching lines.\n\
+    The per_pattern option is used with count to count the matching lines of each pattern, like `foo:12 bar:3`.\n\
+    The files_with_matches option is used to print only the names of the files with a match.\n\
+    The quiet option is used to print nothing and only answer with the exit code.\n\
+    The fail_on_match option is used to exit 1 when a line is selected and 0 when none is, for the CI checks.\n\
+    The offsets_only option is used to print only `line:start-end`, the byte range of each match in its line.\n\
+    The format option is used to print each match with a template, like '{file}:{line}:{col}:{text}'.\n\
+    The section option is used to print the section (same indentation or more) where the pattern is found.\n\
+    The ignore_blank option is used to skip the blank lines, they never match and are never printed.\n\
+    The paragraph option is used to print the paragraph (between blank lines) where the pattern is found.\n\
+    The section_context option is used to print N lines before and after the match, clipped to its section.\n\
     The tabs_c option is used to set the number of spaces for a tab. Default is 4.\n\
-    The debug option is used to print all the args for debug.\n\
+    The filename_match option is used to list the files under path whose path matches the pattern.\n\
+    The summary option is used to print a recap like `3 matches in 1 file (5 lines scanned)` to stderr.\n\
+    The no_messages option is used to print no warnings and no hints on stderr.\n\
+    The scanned option is used to print how many lines were checked against the pattern to stderr.\n\
+    The heatmap option is used to print to stderr where the matches of a file cluster, as bars.\n\
+    The summary_by_file option is used to print `file: count` for every searched file to stderr.\n\
+    The count_files option is used to print `Searched N files` to stderr at the end.\n\
+    The count_files_matched option is used to print `3 files matched` to stderr at the end.\n\
+    The debug option is used to print all the args for debug, on stderr.\n\
     \n\
     Example:\n\
     \n\
     mygrep pattern file.txt\n\
     mygrep regex_pattern file.txt -R\n\
+    mygrep '(?<=user=)\\w+' file.txt -P\n\
     mygrep pattern file.txt -I -c magenta\n\
+    mygrep 'id=\\d+' file.txt -R -o --join ,\n\
+    mygrep -e foo -e bar file.txt --all\n\
+    mygrep -e OPEN -e ERROR file.txt --near 3\n\
+    mygrep ERROR file.txt --and-not timeout\n\
+    mygrep 'name\\tage' file.tsv --interpret-escapes\n\
+    mygrep 500-599 access.log --numeric-range\n\
+    mygrep -R '(?<key>\\w+)=' config.ini -r '\\U${key}\\E: '\n\
+    mygrep pattern file.txt --format '{file}:{line}:{col}:{text}'\n\
+    mygrep pattern file.txt --pretty | less -R\n\
+    mygrep '\\.rs$' . -R --filename-match\n\
     cat file.txt | mygrep pattern \n\
     cat file.txt | mygrep regex_pattern -R\n\
     \n\
+    Commands:\n\
+    \n\
+    When no command is given `search` is implied, so `mygrep pattern file.txt` is the same as `mygrep search pattern file.txt`.\n\
+    To search for a pattern that is also a command name, use `mygrep search search file.txt`.\n\
+    `mygrep completions <shell>` prints the completion script for bash, elvish, fish, powershell or zsh.\n\
+    `mygrep man` prints the man page.\n\
+    \n\
     Exit Codes:
        0: Success
        1: Generic Error
@@ -47,19 +161,230 @@ use std::{process, vec};
     "
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    #[command(flatten)]
+    search: Option<SearchArgs>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Commands {
+    /// Search for a pattern (the default when no command is given)
+    Search(Box<SearchArgs>),
+    /// Print every search option with its default value
+    Config,
+    /// Print the completion script for a shell
+    Completions {
+        /// The shell to generate the script for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page
+    Man,
+    /// Time a few representative searches on a generated file
+    #[command(hide = true)]
+    Bench {
+        /// The number of lines of the generated file
+        #[arg(long, default_value_t = 200_000)]
+        lines: usize,
+        /// How many times each search is run, the best time is kept
+        #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+struct SearchArgs {
     /// The pattern to look for
-    pattern: String,
-    /// The path to the file to read
+    #[arg(required_unless_present_any = ["patterns", "patterns_env", "interactive"])]
+    pattern: Option<String>,
+    /// The path to the file to read, a directory is searched file by file
+    #[arg(long_help = "The path to the file to read, stdin when it is not given. A directory is searched file by file, one at a time in the order of their paths (it is walked sorted by name), so the same tree always gives the same output, whatever the mode, without buffering anything.")]
     path: Option<std::path::PathBuf>,
 
+    /// A pattern to look for, can be repeated, the first positional is then the path
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN")]
+    patterns: Vec<String>,
+
+    /// Read more patterns from this environment variable, one per line, the first positional is then the path
+    #[arg(long, value_name = "VAR")]
+    #[arg(long_help = "Read more patterns from an environment variable, one per line (empty lines are skipped), e.g. a list of forbidden tokens in CI. Any of the patterns of -e and of the variable is enough for a line to match (all of them with all), the -e ones are listed first. Then too the first positional is the path.")]
+    patterns_env: Option<String>,
+
+    /// With more patterns, only the lines containing all of them
+    #[arg(long, default_value_t = false)]
+    all: bool,
+
+    /// Reject the matching lines that also contain this pattern, can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    #[arg(long_help = "Reject the lines that match but also contain the given pattern (repeatable, same regex and insensitive options of the patterns).")]
+    and_not: Vec<String>,
+
+    /// Only look for the pattern in these lines, 1-based and inclusive
+    #[arg(long, value_name = "START:END", value_parser = parse_line_range)]
+    #[arg(long_help = "Look for the pattern only in a range of lines, like 100:200 (1-based, inclusive, 100: or :200 for open ranges). The printed line numbers are still the ones of the file, and the context of a match (after, before, section) can extend outside of the range.")]
+    lines: Option<(usize, usize)>,
+
+    /// Only look for the pattern in the lines stamped at this time or later
+    #[arg(long, value_name = "TIME")]
+    #[arg(long_help = "Triage a log by time: only the lines whose timestamp is in the window (both ends included) are searched, `--since '2024-05-01 10:00:00' --until '2024-05-01 10:30:00'`. The timestamp is read at the start of the line with time_format, the strftime format of chrono (`%Y-%m-%d %H:%M:%S` by default, `[%d/%b/%Y:%H:%M:%S` for an access log), since and until are in the same format or just a date like 2024-05-01. The lines without a timestamp (like the rest of a stack trace) are skipped, unless keep_untimed.")]
+    since: Option<String>,
+
+    /// Only look for the pattern in the lines stamped at this time or before
+    #[arg(long, value_name = "TIME")]
+    until: Option<String>,
+
+    /// The format of the timestamp at the start of the lines, for since and until
+    #[arg(long, value_name = "FORMAT", default_value = "%Y-%m-%d %H:%M:%S")]
+    time_format: String,
+
+    /// With since or until, keep the lines without a timestamp too
+    #[arg(long, default_value_t = false)]
+    keep_untimed: bool,
+
+    /// Parse each line as JSON and search only the value at this dotted path, like request.status
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines", "last", "replace", "extract"])]
+    #[arg(long_help = "For the JSON-lines logs: each line is parsed and the pattern is looked for only in the value at the dotted path (`request.status`, `items.0.name` for the first item of an array), so a key or another field can't match by mistake. The value is printed instead of the line, highlighted, a string without its quotes and anything else as JSON. A line without the field is skipped, one that is not JSON is reported (unless no_messages) and skipped. (Not compatible with the context, section, near, join_lines, last, replace and extract)")]
+    json_field: Option<String>,
+
+    // since and until, parsed with time_format
+    #[arg(skip)]
+    time_window: Option<(NaiveDateTime, NaiveDateTime)>,
+
+    /// Print the lines between two patterns found at most N lines apart
+    #[arg(long, value_name = "N", conflicts_with_all = ["all", "section", "section_context", "after", "before", "context", "window", "only_matching"])]
+    #[arg(long_help = "Find two patterns (given with -e) at most N lines apart: the lines from one to the other are printed, overlapping windows only once.")]
+    near: Option<usize>,
+
+    /// Join every N lines into one before searching, for output wrapped over fixed lines
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["near", "section", "section_context", "after", "before", "context", "window", "only_matching", "format", "files_with_matches", "filename_match"])]
+    #[arg(long_help = "Glue every N lines (1 to N, N+1 to 2N...) into a single line before searching, for records wrapped over a fixed number of lines: a pattern broken by the wrap is found again. The joined line is printed with the number of its first line.")]
+    join_lines: Option<u64>,
+
+    /// Read a unified diff and search only its added lines, numbered in the new file
+    #[arg(long, default_value_t = false, conflicts_with_all = ["section", "section_context", "paragraph", "after", "before", "context", "window", "dedupe_context", "near", "join_lines", "last", "lines", "json_field", "byte_offset", "min_count", "per_pattern", "sort_matches", "files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "Grep a changeset, `git diff | mygrep --diff TODO`: the input is read as a unified diff and only the added (+) lines are searched, without their +, the removed and the context lines are not. The hunk headers give the numbers of the lines in the new file, that are printed (unless no_line_numbers) after the name of the file, `src/main.rs:42: ` (with heading the name is printed once above its lines instead).")]
+    diff: bool,
+
     /// Color to use
     #[arg(short, long, value_enum, default_value_t = Colors::Red)]
     color: Colors,
+
+    /// Color the whole matching line too, the matches keep their own color
+    #[arg(long, value_enum, value_name = "COLOR")]
+    #[arg(long_help = "Tint the whole matching line with a color, to spot the matches while scrolling. The found patterns keep their color and formats, so pick a different one (e.g. --color-line blue with the default red).")]
+    color_line: Option<Colors>,
+
+    /// Color of the line numbers, dimmed by default
+    #[arg(long, value_enum, value_name = "COLOR")]
+    #[arg(long_help = "Style the line numbers and the file names of heading on their own, like the ln and fn of GREP_COLORS: the numbers are dimmed and the names magenta and bold unless a color is given.")]
+    line_number_color: Option<Colors>,
+
+    /// Color of the file names of heading, magenta by default
+    #[arg(long, value_enum, value_name = "COLOR", default_value_t = Colors::Magenta)]
+    filename_color: Colors,
+
+    /// Color only the first match of each line
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Color only the first match of each line, the other ones are printed plain. The line is still printed whole, see only_matching to print just the matches.")]
+    first_match_only: bool,
+
+    /// Color only the first N matches of each line
+    #[arg(long, value_name = "N")]
+    #[arg(long_help = "Color only the first N matches of each line, a line with thousands of them is printed quickly and stays readable. The line still counts once for count and summary.")]
+    max_matches_per_line: Option<usize>,
+
+    /// Select only the lines where the patterns are found at least N times, like the obfuscated ones
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "near")]
+    #[arg(long_help = "Flag the suspicious lines of a security scan, where a pattern is unusually dense (`--min-matches-per-line 5 -R '%[0-9a-f]{2}'` for a heavily encoded URL): only the lines where the patterns are found at least N times (each match of each pattern counts) are selected, with all their matches highlighted. The others are as if they didn't match, for count and invert too.")]
+    min_matches_per_line: Option<u64>,
+
+    /// Color the matches of a line in turn with red, yellow, green, cyan, blue and magenta
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Color the matches of a line in turn with red, yellow, green, cyan, blue and magenta instead of the single color, so matches next to each other can be told apart. Matches that overlap (two patterns on the same text, or overlapping) are highlighted as one, in the color of the one starting first.")]
+    rainbow: bool,
     
+    /// When to use colors, auto is only when printing to a terminal
+    #[arg(long, value_enum)]
+    color_mode: Option<ColorMode>,
+
+    /// Search the text files inside a .zip, named archive.zip:member
+    #[arg(long, default_value_t = false, conflicts_with_all = ["files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "Search inside a .zip without extracting it: each text member is searched like a file named archive.zip:member, under its heading (count prints `archive.zip:member: 3`). The directories and the binary members are skipped. The other files are searched as usual.")]
+    archive: bool,
+
+    /// Run a shell command and search its output instead of a file
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["path", "files_from", "files_from0", "filename_match"])]
+    #[arg(long_help = "Search the output of a command without a pipe, handy on Windows: `mygrep error --command 'cargo build'`. Its stdout is searched like stdin, its stderr is printed as it is. When the command fails mygrep exits with its exit code, otherwise the exit code is the one of the search. The command is run by the shell (sh -c, cmd /C on Windows), so whatever the shell understands (;, |, $(...)) is run too: never build it from untrusted input.")]
+    command: Option<String>,
+
+    /// Search only the files whose path contains this text
+    #[arg(long, value_name = "TEXT")]
+    #[arg(long_help = "Search only the files whose path contains a text, like src/, a simple alternative to globs. The paths are filtered before any file is opened: the files under a directory, the ones listed by files_from, or the path itself. It is case insensitive with insensitive (or smart_case).")]
+    path_contains: Option<String>,
+
+    /// Search the files listed in this file, one per line, - reads the list from stdin
+    #[arg(long, value_name = "LIST", conflicts_with_all = ["files_from0", "filename_match"])]
+    #[arg(long_help = "Search the files listed in a file instead, one path per line (- reads the list from stdin), e.g. the output of find or fd. Each file gets its heading, count prints `file: 3`. The after and before context never goes past the start or the end of a file, a `--` line splits the files with matches. A listed file that can't be read is reported (unless no_messages) and skipped. files_from0 does the same with the paths separated by NUL, for find -print0 and names containing a newline.")]
+    files_from: Option<PathBuf>,
+
+    /// Like files_from, with the paths separated by NUL (find -print0)
+    #[arg(long, value_name = "LIST", conflicts_with = "filename_match")]
+    files_from0: Option<PathBuf>,
+
+    /// Read the file once, then ask for patterns on stdin and print the matches of each one
+    #[arg(long, default_value_t = false, conflicts_with_all = ["patterns", "patterns_env", "files_from", "files_from0", "command", "archive", "near", "replace", "extract", "sort_matches", "count_by", "html", "files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "Explore a big file read only once: `mygrep --interactive app.log` asks for a pattern (the prompt is on stderr), prints its matches and asks for the next one, until an empty line or the end of stdin. The other options (colors, context, insensitive...) stay the same for the whole session. An invalid regex is reported and the next pattern is asked. Given before the path, a pattern is searched before the first question.")]
+    interactive: bool,
+
+    /// Write the results to this file instead of stdout
+    #[arg(short = 'O', long, value_name = "FILE")]
+    #[arg(long_help = "Write the results to a file instead of stdout, the file is created or truncated. The file gets no colors unless color_mode is always (or pretty is used).")]
+    output: Option<PathBuf>,
+
+    /// Flush the output after every line
+    #[arg(long, default_value_t = false)]
+    line_buffered: bool,
+
+    /// Wrap the printed lines at the terminal width, keeping the colors across the breaks
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Break the printed lines at the width of the terminal, a colored match split by the break keeps its color on the next line. It does nothing when the output is piped or written to a file.")]
+    wrap: bool,
+
+    /// Print only RADIUS chars on each side of the matches of a line, for the very long lines
+    #[arg(long, value_name = "RADIUS", conflicts_with_all = ["html", "replace", "only_matching", "format", "offsets_only", "extract"])]
+    #[arg(long_help = "For the very long lines (a minified file, a JSON dump): only the RADIUS chars before and after each match are printed, with … (... with ascii) where the line is cut, `…the key \"token\": \"ab…`. The snippets of the close matches of a line, overlapping, are merged, the others printed one after the other on the same line. The context lines are printed whole, an inverted line (no match) too.")]
+    snippet: Option<usize>,
+
+    /// Print the matching lines as a standalone HTML document, the matches styled inline
+    #[arg(long, default_value_t = false, conflicts_with_all = ["only_matching", "format", "offsets_only", "extract", "replace", "sort_matches", "count", "section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines", "files_with_matches", "quiet", "filename_match", "wrap"])]
+    #[arg(long_help = "Print the matching lines as a standalone HTML page, to paste the results in a wiki or an email with the highlighting: each match is a <span> styled inline like in the terminal (color, rainbow, bold, underline, italic, strike, color_line), the text is HTML-escaped. With line_numbers and heading the numbers and the file names are in the page too. (Only the matching lines, not compatible with the context and the other output modes)")]
+    html: bool,
+
+    /// Stop printing before the output goes past N bytes
+    #[arg(long, value_name = "N")]
+    #[arg(long_help = "Stop printing before the output (stdout or the output file) goes past N bytes, the colors included, with a notice on stderr: a safety valve for a pattern found everywhere in a huge input. The line that doesn't fit is not printed at all, the summaries on stderr still are.")]
+    max_output_bytes: Option<usize>,
+
+    /// Search a file bigger than SIZE line by line instead of reading it whole, like 512M
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    #[arg(long_help = "Search an unknown input without running out of memory: a file bigger than SIZE (bytes, or with a K, M or G suffix of 1024) is read line by line like stdin, the smaller ones are read whole as usual. The modes that need the whole file (section, paragraph, near, join_lines, last, min_count, record_delimiter) are turned off for it with a warning, the lines are then searched and printed one by one.")]
+    limit_memory: Option<u64>,
+
+    /// Skip the lines longer than N bytes, like the minified files and the data blobs
+    #[arg(long, value_name = "N", value_parser = parse_size)]
+    #[arg(long_help = "Keep a multi-megabyte line (a minified .js, a data blob) from slowing the search down: a line longer than N bytes (or with a K, M or G suffix) is skipped before any matching, as if it were not there. It is still printed as the context of a match. With scanned the skipped lines are counted too, `Scanned 40 lines, skipped 2 longer than 10000 bytes`.")]
+    max_line_length: Option<u64>,
+
     /// Bold
-    #[arg(short = 'B', long, default_value_t = true)]
+    #[arg(long, default_value_t = true, overrides_with = "no_bold")]
     bold: bool,
 
+    /// Don't bold the matches
+    #[arg(long, default_value_t = false, overrides_with = "bold")]
+    no_bold: bool,
+
     /// Underline
     #[arg(short, long, default_value_t = false)]
     underline: bool,
@@ -73,38 +398,363 @@ struct Cli {
     strike: bool,
 
     /// Show LineNumbers
-    #[arg(short, long, default_value_t = false)]
+    #[arg(short, long, default_value_t = false, overrides_with = "no_line_numbers")]
     line_numbers: bool,
 
+    /// Don't show the line numbers, overrides line_numbers
+    #[arg(long, default_value_t = false, overrides_with = "line_numbers")]
+    #[arg(long_help = "Turn line_numbers off again, like no_heading, no_bold, no_insensitive, no_smart_case and no_section do for theirs, e.g. when a default comes from an environment variable like MYGREP_SMART_CASE, from pretty or from a shell alias. When both are given the last one wins: `-l --no-line-numbers` prints no line numbers, `--no-line-numbers -l` does. no_smart_case always wins, since smart_case can come from the environment.")]
+    no_line_numbers: bool,
+
+    /// Print the 1-based byte column of the first match after the line number
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Print the column of the first match after the line number, like `7: 12: text` (of each match with only_matching). It is the 1-based byte column: a tab is one column and a multi-byte char counts all its bytes, like the columns of other tools and compilers.")]
+    column: bool,
+
+    /// Like column, but tabs count as tabs_c columns and every other char as one
+    #[arg(long, default_value_t = false)]
+    column_visual: bool,
+
+    /// Print the file name once above its matches
+    #[arg(long, default_value_t = false, overrides_with = "no_heading")]
+    heading: bool,
+
+    /// Don't print the file name above its matches, overrides heading
+    #[arg(long, default_value_t = false, overrides_with = "heading")]
+    no_heading: bool,
+
+    // the inputs are several files (files_from, a walked directory, the members of archive),
+    //   each is named by count
+    #[arg(skip)]
+    several_inputs: bool,
+
+    // only the options of PLAIN_OPTIONS are given, the lines are matched by for_each_match
+    #[arg(skip)]
+    plain: bool,
+
+    /// Split the input into records on this instead of lines, like \x1e
+    #[arg(long, value_name = "STR", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    #[arg(long_help = "For the logs whose entries are not lines: the input is split on the given string (its escapes like \\x1e or \\n are interpreted) and each record is searched, highlighted, counted and printed as a unit, its newlines included. The line numbers are the 1-based number of the record, and the context is made of records too. The delimiter is not part of the records, for `---` lines use `--record-delimiter '\\n---\\n'`, the newline at the end of the input is dropped. The input is read whole first.")]
+    record_delimiter: Option<String>,
+
+    /// End the printed file names with NUL instead of a newline
+    #[arg(short = 'Z', long, default_value_t = false)]
+    #[arg(long_help = "End the printed file names (the heading, and the paths listed by filename_match) with a NUL byte instead of a newline, so a name containing a newline can be told apart from the lines around it, e.g. for xargs -0.")]
+    null: bool,
+
+    /// Heading, line numbers and color even when piped
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Turn on heading and line_numbers, and keep the colors even when the output is piped (e.g. to less -R). It only adds to the other options, which keep their own effect, and no_heading or no_line_numbers still turns its part off: `--pretty --no-line-numbers` prints no line numbers.")]
+    pretty: bool,
+
+    /// Plain ASCII output, no colors or escape codes
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "For terminals that can't show colors: no escape codes are printed at all (even with pretty) and the gutter is drawn with |. On Windows the colors are enabled in the legacy console (cmd.exe) at startup, and turned off if that fails.")]
+    ascii: bool,
+
+    /// Separate LineNumbers from the line with a gutter
+    #[arg(long, default_value_t = false)]
+    gutter: bool,
+
+    /// Mark context lines with '-' and matching lines with ':'
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Mark context lines (after, before and section) with `-` and matching lines with `:`, after the line number if shown.")]
+    context_indent: bool,
+
+    /// Print this after the line number and the byte offset instead of ': ', like '\t' for columns
+    #[arg(long, visible_alias = "output-separator", value_name = "STR", conflicts_with = "gutter")]
+    #[arg(long_help = "Print the fields of a line for the columnar tools: it goes after the line number, the byte offset and the file name (of diff) in place of `: ` (and of `- ` with context_indent, the context lines get the same one), `--field-separator '\\t'` prints `42\\tthe line`. Its escapes are interpreted like those of pre. (Not compatible with gutter)")]
+    field_separator: Option<String>,
+
     /// Pattern is a Regex
     #[arg(short = 'R', long, default_value_t = false)]
     regex: bool,
 
+    /// Pattern is a PCRE-like Regex (lookaround, backreferences)
+    #[arg(short = 'P', long, default_value_t = false)]
+    pcre: bool,
+
+    /// Regex engine, default is the fast one, fancy has lookaround and backreferences (same as pcre)
+    #[arg(long, value_enum, value_name = "ENGINE")]
+    #[arg(long_help = "Pick the engine of a regex pattern explicitly (implies regex): default is the regex crate, linear time and the fastest, fancy is fancy-regex, with lookaround and backreferences but it can backtrack (the same as pcre).")]
+    regex_engine: Option<RegexEngine>,
+
+    /// Turn \t, \n, \0 and \xNN in a literal pattern into the chars they stand for
+    #[arg(long, default_value_t = false, conflicts_with_all = ["regex", "pcre"])]
+    #[arg(long_help = "Turn \\t, \\n, \\r, \\0, \\\\ and \\xNN (the char U+00NN) in a literal pattern into the chars they stand for, like printf. Other backslashes are kept. A line never contains \\n, since the input is split on it. (Not compatible with regex, which has its own escapes)")]
+    interpret_escapes: bool,
+
+    /// The pattern is a LOW-HIGH range, like 500-599, matching the numbers in it
+    #[arg(long, default_value_t = false, conflicts_with_all = ["regex", "pcre", "regex_engine", "overlapping", "word", "interpret_escapes", "replace"])]
+    #[arg(long_help = "Look for the numbers in a range instead of a text, e.g. the HTTP status codes with 500-599 (or [500-599]), inclusive: every run of digits of the line is read as a number and the ones in the range are the matches, a whole run or nothing (the 500 of 15000 is not one) and without a sign (-502 has the match 502). A range that is not LOW-HIGH exits with code 2.")]
+    numeric_range: bool,
+
+    /// Match the text at most N edits (Levenshtein) away from the literal pattern, for the typos
+    #[arg(long, value_name = "N", conflicts_with_all = ["regex", "pcre", "regex_engine", "overlapping", "word", "numeric_range", "replace", "extract"])]
+    #[arg(long_help = "Find a literal pattern with typos: a line matches when some part of it is at most N edits (a char added, removed or changed) away from the pattern, `--fuzzy 1 connection` finds `conection` and `connnection` (`connectoin`, two swapped chars, is 2 edits). The part closest to the pattern is highlighted, once per line. It compares the pattern with every position of the line, so it is much slower than the exact search, and a N as big as the pattern matches about anything.")]
+    fuzzy: Option<usize>,
+
+    /// Select the lines that don't contain the pattern
+    #[arg(short = 'v', long, default_value_t = false, conflicts_with_all = ["only_matching", "format", "near"])]
+    #[arg(long_help = "Select the lines that don't contain the pattern, their line numbers are still their position in the file (not compatible with only_matching, format and near, there is no match to print). With after, before or context the selected lines are the non-matching ones and their context the lines around them (that contain the pattern), not highlighted: every line is printed once, in the order of the file, with a : after its number when it is selected and a - when it is context (with context_indent). `-v -A 1 x` on a, x, b, x, x prints 1 a, 2 x, 3 b, 4 x.")]
+    invert: bool,
+
+    /// Find overlapping occurrences of a literal pattern (not with regex)
+    #[arg(long, default_value_t = false, conflicts_with_all = ["regex", "pcre"])]
+    overlapping: bool,
+
+    /// Match only whole words
+    #[arg(short, long, default_value_t = false)]
+    word: bool,
+
+    /// Only ASCII letters, digits and _ are word characters (with word)
+    #[arg(long, default_value_t = false, requires = "word")]
+    word_ascii: bool,
+
     /// Case Insensitive
-    #[arg(short = 'I', long, default_value_t = false)]
+    #[arg(short = 'I', long, default_value_t = false, overrides_with = "no_insensitive")]
     insensitive: bool,
 
-    /// After
-    #[arg(short, long, default_value_t = 0)]
+    /// Case sensitive, overrides insensitive
+    #[arg(long, default_value_t = false, overrides_with = "insensitive")]
+    no_insensitive: bool,
+
+    /// Case insensitive unless the pattern contains an uppercase letter
+    #[arg(long, default_value_t = false, env = "MYGREP_SMART_CASE", value_parser = clap::builder::BoolishValueParser::new())]
+    #[arg(long_help = "Search case insensitively unless the pattern contains an uppercase letter, like ripgrep: `error` finds Error and ERROR, `Error` finds only Error. Each pattern given with -e is checked on its own, the escapes of a regex like \\W or \\p{Lu} don't count as uppercase. Set MYGREP_SMART_CASE=1 to make it the default (MYGREP_SMART_CASE=0 turns it off again).")]
+    smart_case: bool,
+
+    /// Don't use smart_case, overrides smart_case and MYGREP_SMART_CASE
+    #[arg(long, default_value_t = false)]
+    no_smart_case: bool,
+
+    /// After, like grep -A
+    #[arg(short = 'A', long, default_value_t = 0)]
     after: usize,
 
-    /// Before
-    #[arg(short, long, default_value_t = 0)]
+    /// Before, like grep -B
+    #[arg(short = 'B', long, default_value_t = 0)]
     before: usize,
 
+    /// Lines before and after the match, like grep -C (-A and -B win over it)
+    #[arg(short = 'C', long, value_name = "N", conflicts_with = "section")]
+    #[arg(long_help = "Print the same number of lines before and after the match, an explicit after or before wins over it. The short flags are the ones of grep: -A after, -B before and -C context, bold has only the long --bold.")]
+    context: Option<usize>,
+
+    /// Print the context shared by close matches once, as a single block
+    #[arg(long, default_value_t = false, conflicts_with_all = ["section", "section_context", "paragraph"])]
+    #[arg(long_help = "Read close matches as one region: the lines of context of a match that are also the context (or a match) of the next one are printed once, in order, without any separator, so a cluster of matches is a single block. The -- between the files stays. The context of a file and of stdin is printed this way without it too, the option is kept for the scripts that give it.")]
+    dedupe_context: bool,
+
+    /// Lines before and after the match in one token, like 2:5, the shared ones printed once
+    #[arg(long, value_name = "BEFORE:AFTER", value_parser = parse_window, conflicts_with_all = ["after", "before", "context", "section", "section_context", "paragraph"])]
+    #[arg(long_help = "Give the context in one token, `--window 2:5` is `-B 2 -A 5 --dedupe-context` with a `--` line between the blocks that don't touch: both numbers are needed (0 for no lines on that side), anything else is an error.")]
+    window: Option<(usize, usize)>,
+
+    /// Search the inputs that are not UTF-8 too, like grep -a
+    #[arg(short = 'a', long, default_value_t = false)]
+    #[arg(long_help = "Search an input that is not valid UTF-8 (a binary file, a log in Latin-1) instead of stopping with an error: the bytes that are not UTF-8 are read as the replacement char �. With archive the binary members are searched too.")]
+    text: bool,
+
+    /// Keep the UTF-8 BOM at the start of a file, it is stripped by default
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Keep the UTF-8 BOM (the bytes EF BB BF) that Windows editors put at the start of a file. By default it is stripped before searching, of stdin and of the command output too, so `^` still anchors the first line and the BOM is never printed. The byte offsets start after it.")]
+    keep_bom: bool,
+
+    /// Remove the ANSI escape codes (colors) of the input before searching it
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Search again an output that was saved with its colors (`mygrep --color-mode always ... > out.txt`, a CI log): the escape codes (colors, cursor moves, the OSC links) are removed from every line before searching, so they can't split a word the pattern is looking for. The lines are printed without them too, with the highlight of this search.")]
+    strip_ansi: bool,
+
+    /// Print the byte offset of each line in the input before it, like grep -b
+    #[arg(short = 'b', long, default_value_t = false, conflicts_with_all = ["offsets_only", "format"])]
+    #[arg(long_help = "Print the 0-based byte offset where each printed line starts in the input, after the line number: `7: 1520: text`. With text the offsets are the ones of the decoded text.")]
+    byte_offset: bool,
+
+    /// Print the matching lines with the matches replaced, $1 ${name} \U \L \E are supported
+    #[arg(short = 'r', long, value_name = "REPLACEMENT", requires = "regex", conflicts_with_all = ["pcre", "only_matching", "format"])]
+    #[arg(long_help = "Print the matching lines with every match of the regex replaced (the file is not changed). In the replacement $1, ${1}, $name and ${name} are the capture groups and $$ is a $, \\U and \\L turn what follows to uppercase or lowercase until \\E. Other backslashes are kept. (Needs regex, not compatible with pcre, only_matching and format)")]
+    replace: Option<String>,
+
+    /// Write the files with the matches replaced into this directory, mirroring their paths, instead of printing them
+    #[arg(long, value_name = "DIR", requires_all = ["replace", "path"], conflicts_with_all = ["invert", "files_from", "files_from0", "command", "archive", "interactive", "files_with_matches", "quiet", "count"])]
+    #[arg(long_help = "Preview a replace over a tree: each file with a match is written with its matches replaced under DIR at the same relative path, the originals are untouched. A file that exists there is skipped unless overwrite.")]
+    output_dir: Option<PathBuf>,
+
+    /// Replace the files that already exist in output_dir, they are skipped by default
+    #[arg(long, default_value_t = false, requires = "output_dir")]
+    overwrite: bool,
+
+    /// Print the named groups of each match as tab separated columns, like --extract ip,status
+    #[arg(long, value_name = "NAMES", value_delimiter = ',', requires = "regex", conflicts_with_all = ["pcre", "invert", "replace", "only_matching", "format", "offsets_only", "sort_matches", "count", "section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines"])]
+    #[arg(long_help = "Print the named groups of each match of a regex, tab separated in the given order: `-R '(?<ip>\\S+) .* (?<status>\\d{3}) ' --extract ip,status` prints the ip and the status of every line of an access log. A group that didn't take part in the match is an empty column, a name that is not in the pattern is an error. (Needs regex, not compatible with pcre and the other output modes)")]
+    extract: Option<Vec<String>>,
+
+    /// Print only the matched parts of the line
+    #[arg(short, long, default_value_t = false, conflicts_with_all = ["section", "after", "before", "context", "window"])]
+    only_matching: bool,
+
+    /// Join the matches of a line with this separator (with only_matching)
+    #[arg(long, requires = "only_matching")]
+    join: Option<String>,
+
+    /// Print this before each matching line, after its line number, like <mark>
+    #[arg(long, value_name = "STR", conflicts_with_all = ["only_matching", "format", "offsets_only", "extract", "count"])]
+    #[arg(long_help = "Bracket each matching line, e.g. `--pre '<mark>' --post '</mark>'` or `--pre '**' --post '**'` for markdown. They go between the line number and the colored line, so `-l` prints `3: <mark>text</mark>`, the context lines are left as they are. The escapes \\t, \\n, \\xNN... are interpreted like with interpret_escapes.")]
+    pre: Option<String>,
+
+    /// Print this after each matching line, like </mark>
+    #[arg(long, value_name = "STR", conflicts_with_all = ["only_matching", "format", "offsets_only", "extract", "count"])]
+    post: Option<String>,
+
+    /// Print only the name of the file if it matches, reading it up to the first match
+    #[arg(long, default_value_t = false, conflicts_with_all = ["count", "only_matching", "section", "section_context", "after", "before", "context", "window", "near", "filename_match", "format"])]
+    #[arg(long_help = "Print only the name of the file (- for stdin) when the pattern is found in it. The file is read line by line and the search stops at the first match, the rest of a large file is never read. When path is a directory, every file under it is searched and the matching ones are listed.")]
+    files_with_matches: bool,
+
+    /// Print nothing, exit 0 at the first match or 1 if the pattern is not found
+    #[arg(short, long, default_value_t = false, conflicts_with_all = ["files_with_matches", "count", "filename_match"])]
+    #[arg(long_help = "Print nothing and only answer with the exit code, for the conditions of scripts: 0 as soon as the pattern is found, without reading the rest (when path is a directory, the remaining files are skipped), 1 when no file contains it, 2 for an invalid regex.")]
+    quiet: bool,
+
+    /// Exit 1 if the pattern is found and 0 if it isn't, for the lint checks
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Assert that a forbidden token is not there, in a pre-commit hook or a CI check: the exit code is 1 when a line is selected and 0 when none is, the lines are still printed. With quiet it exits 1 at the first match. An invalid regex is still 2.")]
+    fail_on_match: bool,
+
+    /// Print only the last matching line, with its context
+    #[arg(long, default_value_t = false, conflicts_with_all = ["near", "join_lines", "files_with_matches", "quiet"])]
+    #[arg(long_help = "Print only the last matching line of the file, with its context (after, before, section), e.g. the final error of a log and what followed it. The file is searched from its end, with a directory each file would get its own last match.")]
+    last: bool,
+
+    /// Print the found patterns sorted, with count once each after their number
+    #[arg(long, default_value_t = false, conflicts_with_all = ["invert", "only_matching", "format", "offsets_only", "replace", "section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines", "files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "Print the found patterns (like only_matching) of all the searched files sorted, after the search. With count each one is printed once after the number of times it was found, like `grep -o | sort | uniq -c`.")]
+    sort_matches: bool,
+
+    /// Count the matching lines by the first group of this regex, printed as `key: count` most common first
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["invert", "only_matching", "format", "offsets_only", "replace", "extract", "sort_matches", "count", "section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines", "diff", "files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "Aggregate a log, like the status codes or the users: `mygrep GET --count-by 'HTTP/1.1\" (\\d+)' access.log` counts the matching lines by the first group of the regex, or by the whole match of a regex without groups, and prints a `key: count` table of all the searched files, the most common key first. Every match of the regex in a line is counted, a line where it isn't found isn't.")]
+    count_by: Option<String>,
+
+    // count_by, compiled once
+    #[arg(skip)]
+    count_by_regex: Option<Regex>,
+
+    /// Print only the number of matching lines
+    #[arg(long, default_value_t = false, conflicts_with_all = ["only_matching", "section", "section_context", "after", "before", "context", "window", "near", "filename_match", "format"])]
+    count: bool,
+
+    /// With count over several files, print the files without a match too, as `file: 0`
+    #[arg(long, default_value_t = false, requires = "count")]
+    #[arg(long_help = "With count over several files (a directory, files_from, archive), print the ones without a match too, as `file: 0`.")]
+    include_zero: bool,
+
+    /// Leave out the files with fewer than N matching lines
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["near", "join_lines"])]
+    #[arg(long_help = "Find the files where a pattern is common, not just there: a file with fewer than N matching lines is left out as if it had none, its lines aren't printed, files_with_matches doesn't list it and count doesn't print its number. The lines are counted before anything is printed, so stdin and the named pipes are read whole first. (Not compatible with near and join_lines)")]
+    min_count: Option<u64>,
+
+    /// With count, the number of matching lines of each pattern, like foo:12 bar:3
+    #[arg(long, default_value_t = false, requires = "count", conflicts_with_all = ["invert", "join_lines", "sort_matches"])]
+    #[arg(long_help = "Count the matching lines of each pattern given with -e on its own, `foo:12 bar:3` in the order of the patterns, e.g. to see which of some error signatures is the most common. A line with more of the patterns counts for each of them, so the numbers can add up to more than the matching lines. (Requires count)")]
+    per_pattern: bool,
+
+    /// Print only `line:start-end`, the byte range of each match in its line
+    #[arg(long, default_value_t = false, conflicts_with_all = ["invert", "only_matching", "format", "count", "section", "section_context", "paragraph", "after", "before", "context", "window", "near", "join_lines", "replace"])]
+    #[arg(long_help = "Print only the position of each match, `line:start-end`: the 1-based line number and the byte range of the match in the line (0-based, end excluded, like the slice line[start..end]), for tools that read the text from the file themselves.")]
+    offsets_only: bool,
+
+    /// Print each match with a template: {file} {line} {col} {vcol} {match} {text}
+    #[arg(long, conflicts_with_all = ["only_matching", "section", "section_context", "after", "before", "context", "window", "near"])]
+    #[arg(long_help = "Print each match with a template, the placeholders are {file} (- for stdin), {line}, {col} (1-based byte column), {vcol} (the column seen in an editor, like column_visual), {match} and {text} (the whole line). Use {{ and }} for literal braces.")]
+    format: Option<String>,
+
     /// Section
-    #[arg(short = 'S', long, default_value_t = false)]
+    #[arg(short = 'S', long, default_value_t = false, overrides_with = "no_section")]
     section: bool,
 
+    /// Don't show the section, overrides section
+    #[arg(long, default_value_t = false, overrides_with = "section")]
+    no_section: bool,
+
+    /// Lines of context around the match, clipped to its section (implies section)
+    #[arg(long)]
+    #[arg(long_help = "Print only N lines before and after the match, clipped to the section where the pattern is found: the lines go from the head of the section (the first line before the match with less indentation) to the last line with the same indentation or more, never outside of it. (Implies section, not compatible with after and or before)")]
+    section_context: Option<usize>,
+
+    /// Print the whole paragraph, delimited by blank lines, around the match
+    #[arg(long, default_value_t = false, conflicts_with_all = ["section", "section_context", "after", "before", "context", "window", "only_matching", "format", "count", "near", "join_lines"])]
+    #[arg(long_help = "Print the paragraph where the pattern is found, like the paragraph mode of awk: the lines between the blank lines (only whitespace counts as blank) before and after the match. A paragraph with more matches is printed once. (Not compatible with section, after and before)")]
+    paragraph: bool,
+
+    /// Skip the blank lines: never matched, counted or shown as context
+    #[arg(long, default_value_t = false, conflicts_with = "join_lines")]
+    #[arg(long_help = "Skip the blank lines (only whitespace counts as blank) of a sparse file: they are never a match, even of a pattern like ^ or \\s*, never counted, scanned or printed as context. Inside a section a blank line is skipped too, so it doesn't end the section any more. The paragraphs are still delimited by the blank lines, only they are never printed. (Not compatible with join_lines)")]
+    ignore_blank: bool,
+
     /// Tabs count
     #[arg(short, long, default_value_t = 4)]
     tabs_c: usize,
 
+    /// Match the pattern against the file names under path instead of their contents
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "List the files under path (default current directory, recursively) whose path matches the pattern, honoring regex and insensitive.")]
+    filename_match: bool,
+
+    /// Print a one-line recap of matches and scanned lines to stderr
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Print a one-line recap like `3 matches in 1 file (5 lines scanned)` to stderr. With files_with_matches and quiet each file stops at its first match, which is the one counted.")]
+    summary: bool,
+
+    /// Print the number of matches of each file to stderr, most matches first
+    #[arg(long, default_value_t = false, conflicts_with_all = ["files_with_matches", "quiet"])]
+    #[arg(long_help = "Print `file: count` for every searched file to stderr after the results, the files with more matches first (stdin is -). (Not compatible with files_with_matches and quiet, they stop at the first match of a file)")]
+    summary_by_file: bool,
+
+    /// Print where the matches of each file are, as a bar chart of 20 parts of it, to stderr
+    #[arg(long, default_value_t = false, conflicts_with_all = ["near", "join_lines", "files_with_matches", "quiet", "filename_match"])]
+    #[arg(long_help = "See where the matches of a file cluster, e.g. the errors of a log that bunch up in time: the file is split into 20 parts of the same number of lines (fewer for a short file) and a bar for each part, as long as its matching lines, is printed to stderr after the results of the file.")]
+    heatmap: bool,
+
+    /// Don't print warnings, hints and unreadable directories to stderr
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Keep stderr for the results of the other options: no warnings, no hint to try -R when a literal pattern with regex characters finds nothing, no unreadable directories.")]
+    no_messages: bool,
+
+    /// Print the number of lines checked against the pattern to stderr
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Print how many lines were checked against the pattern to stderr, like `Scanned 40 lines`. The lines skipped by an early exit (past the end of lines) are not counted, to compare how soon a search stops.")]
+    scanned: bool,
+
+    /// Print the number of files searched to stderr
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Print `Searched N files` to stderr at the end, the paths walked by filename_match or the single file (or stdin) searched.")]
+    count_files: bool,
+
+    /// Print the number of files with a match to stderr
+    #[arg(long, default_value_t = false)]
+    #[arg(long_help = "Print `3 files matched` to stderr at the end, to know how many files are affected: the files with at least one matching line (or the paths matching with filename_match) counted once however many lines match. The stdout stays the list of files_with_matches, for xargs.")]
+    count_files_matched: bool,
+
     /// Debug
     #[arg(short, long, default_value_t = false)]
     debug: bool,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum RegexEngine {
+    Default,
+    Fancy,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
 enum Colors{
     Red,
@@ -124,247 +774,2702 @@ enum Colors{
     BrightWhite
 }
 
-fn line_contains_pattern(line: &str, pattern: &str) -> Result<bool, Box<dyn Error>> {
-    // return all start indices where pattern is found
-    Ok(line.contains(pattern))
+fn indentation(line: &str, tabs_c: usize) -> usize {
+    // return the number of spaces at the beginning of the line
+    let spaces = line.chars().take_while(|&c| c == ' ').count();
+    let tabs = line.chars().take_while(|&c| c == '\t').count() * tabs_c;
+
+    spaces + tabs
+
 }
 
-fn regex_match_line(line: &str, regex_pattern: &str) -> Result<bool, Box<dyn Error>> {
-    let re = Regex::new(regex_pattern)?;
-    Ok(re.is_match(line))
-    
+fn visual_column(line: &str, start: usize, tabs_c: usize) -> usize {
+    // the 1-based column of the byte start as seen in an editor, a tab is tabs_c
+    //   columns like in indentation, any other char (multi-byte ones too) is one
+    line[..start].chars().map(|c| if c == '\t' { tabs_c } else { 1 }).sum::<usize>() + 1
 }
 
-fn get_normal_indexes(line: &str, pattern: &str) -> Vec<(usize, usize)> {
-    // return a Vec of (start,end) indices where pattern is found
-    let mut indexes : Vec<(usize,usize)> = Vec::new();
-    line.match_indices(pattern).for_each(|start| {
-        indexes.push((start.0, start.0 + pattern.len()));
-    });
-    indexes
+fn column_prefix(line: &str, start: usize, args: &SearchArgs) -> String {
+    // "12: " with column (byte column) or column_visual (editor column), empty otherwise
+    if args.column_visual {
+        return format!("{}: ", visual_column(line, start, args.tabs_c));
+    }
+    if args.column {
+        return format!("{}: ", start + 1);
+    }
+    String::new()
 }
 
-fn get_grep_indexes(line: &str, regex_pattern: &str) -> Vec<(usize, usize)>{
-    // return a Vec of (start,end) indices where regex pattern match
+/// The colors of rainbow, one for each match of a line, starting over after the last.
+const RAINBOW: [Colors; 6] = [Colors::Red, Colors::Yellow, Colors::Green, Colors::Cyan, Colors::Blue, Colors::Magenta];
 
-    let mut indexes : Vec<(usize,usize)> = Vec::new();
-    let re = Regex::new(regex_pattern).unwrap();
-    re.find_iter(line).for_each(|m| {
-        indexes.push((m.start(), m.end()));
-    });
-    
-    indexes
+fn match_color(args: &SearchArgs, nth: usize) -> Colors {
+    // the color of the nth match of a line
+    if args.rainbow {
+        RAINBOW[nth % RAINBOW.len()]
+    } else {
+        args.color
+    }
 }
 
-fn indentation(line: &str, tabs_c: usize) -> usize {
-    // return the number of spaces at the beginning of the line
-    let spaces = line.chars().take_while(|&c| c == ' ').count();
-    let tabs = line.chars().take_while(|&c| c == '\t').count() * tabs_c;
+fn style_pattern(pattern: &str, args: &SearchArgs) -> String {
+    style_pattern_in(pattern, args.color, args)
+}
 
-    spaces + tabs
+fn style_pattern_in(pattern: &str, color: Colors, args: &SearchArgs) -> String {
+    // apply color and all the enabled formats to a found pattern
+    let mut styled = pattern.color(format!("{:?}", color));
+    if args.bold {
+        styled = styled.bold();
+    }
+    if args.underline {
+        styled = styled.underline();
+    }
+    if args.italic {
+        styled = styled.italic();
+    }
+    if args.strike {
+        styled = styled.strikethrough();
+    }
+    styled.to_string()
+}
 
+fn is_insensitive(args: &SearchArgs, pattern: &str) -> bool {
+    // with smart_case, a pattern is case insensitive only when it is all lowercase, like ripgrep
+    //   the escapes of a regex (\W, \S, \p{Lu}) are not uppercase letters to match
+    args.insensitive || (args.smart_case && !has_uppercase_literal(pattern, args.regex || args.pcre))
 }
 
-fn main() -> Result<()> {
-    let mut args = Cli::parse();
+fn search_options(args: &SearchArgs, patterns: &[String]) -> Vec<SearchOptions> {
+    // the library options equivalent to the command line ones, one for each pattern
+    patterns.iter().map(|pattern| SearchOptions {
+        pattern: pattern.clone(),
+        regex: args.regex,
+        insensitive: is_insensitive(args, pattern),
+        pcre: args.pcre,
+        overlapping: args.overlapping,
+        word: args.word,
+        word_ascii: args.word_ascii,
+        numeric_range: args.numeric_range,
+        fuzzy: args.fuzzy,
+    }).collect()
+}
 
-    // print all args for debug
-    if args.debug {
-        println!("----------------------------");
-        println!("args.pattern:      {}", args.pattern);
-        println!("args.path:         {:?}", args.path);
-        println!("args.color:        {:?}", args.color);
-        println!("args.bold:         {}", args.bold);
-        println!("args.underline:    {}", args.underline);
-        println!("args.italic:       {}", args.italic);
-        println!("args.strike:       {}", args.strike);
-        println!("args.line_numbers: {}", args.line_numbers);
-        println!("args.regex:        {}", args.regex);
-        println!("args.insensitive:  {}", args.insensitive);
-        println!("args.after:        {}", args.after);
-        println!("args.before:       {}", args.before);
-        println!("args.section:      {}", args.section);
-        println!("args.debug:        {}", args.debug);
-        println!("----------------------------");
-        println!();
-    }
-
-    // init content as mut empty
-    let mut content = String::new();
-    // if path is not None, do the following
-    if let Some(path) = args.path {
-        content = std::fs::read_to_string(&path)
-            .with_context(|| format!("could not read file `{}`", path.display()))?;
-    }
-    else{
-        // if no path is give we hope to have a stdin as content
-        let stdin = io::stdin();
-        for line in stdin.lock().lines() {
-            content.push_str(&line?);
-            content.push_str("\n");
+// the options of a plain search, `mygrep pattern file`: they only change how the pattern is
+//   compiled or how a matching line is printed, so the library can find the lines
+//   SearchArgs is the group clap makes of all of them
+const PLAIN_OPTIONS: [&str; 34] = [
+    "SearchArgs", "pattern", "path", "patterns", "insensitive", "no_insensitive", "smart_case", "no_smart_case",
+    "regex", "pcre", "regex_engine", "word", "word_ascii", "overlapping", "fuzzy", "numeric_range",
+    "interpret_escapes", "color", "bold", "no_bold", "underline", "italic", "strike", "color_line",
+    "line_numbers", "no_line_numbers", "line_number_color", "filename_color", "heading", "no_heading",
+    "color_mode", "ascii", "gutter", "no_messages",
+];
+
+fn plain_search(matches: &ArgMatches) -> bool {
+    // the options that are not given keep their default, like those that come from no environment variable
+    matches.ids().all(|id| {
+        matches.value_source(id.as_str()) == Some(ValueSource::DefaultValue) || PLAIN_OPTIONS.contains(&id.as_str())
+    })
+}
+
+/// The compiled patterns to look for and the ones that exclude a line.
+struct Patterns {
+    searches: Vec<Matcher>,
+    exclusions: Vec<Matcher>,
+    all: bool,
+    // min_matches_per_line, 1 when not given
+    min_matches: usize,
+}
+
+impl Patterns {
+    fn find(&self, line: &str) -> Option<Vec<Span>> {
+        // the indexes of the patterns found in the line, unless an and_not pattern is found too
+        //   or they are found fewer than min_matches times (a zero-width match like ^ selects the
+        //   line but is not counted)
+        let indexes = find_patterns(line, &self.searches, self.all)?;
+        let too_few = self.min_matches > 1 && indexes.iter().filter(|(start, end)| start < end).count() < self.min_matches;
+        if self.excluded(line) || too_few {
+            return None;
         }
+        Some(indexes)
     }
 
-    // If section is true and before and after are != 0, print warning and set after and before to 0
-    if args.section && (args.before != 0 || args.after != 0) {
-        eprintln!("Section is not compatible with after and or before. Ignoring After and or Before.");
-        args.after = 0;
-        args.before = 0;
+    fn excluded(&self, line: &str) -> bool {
+        find_patterns(line, &self.exclusions, false).is_some()
     }
-    // the section behaviour could print multiple time the same section, this avoid this behavior.
-    let mut sections_to_print: Vec<usize> = vec![];
-    // init a found_rows to keep colored lines to be printed with found pattern, a tuple of index and string
-    let mut found_rows: Vec<(usize, String)> = vec![];
 
-    // color must be string to use it in replace
-    let color = format!("{:?}", args.color);
-
-    //for (index , line) in content.lines().enumerate() {
-    for (index, line) in content.lines().enumerate() {
-
-        let mut search_line = line.to_string().clone();
-        let mut pattern_to_search = args.pattern.clone();
-        let mut get_indexes_f: fn(&str, &str) -> Vec<(usize, usize)> = get_normal_indexes;
-
-        if args.insensitive {
-            search_line = search_line.to_lowercase();
-            pattern_to_search = pattern_to_search.to_lowercase();
-        }
-
-        let mut search_f: fn(&str, &str) -> Result<bool, Box<dyn Error>> = line_contains_pattern;
-
-        if args.regex {
-            search_f = regex_match_line;
-            get_indexes_f = get_grep_indexes;
-        }
-        
-        match search_f(&search_line, &pattern_to_search) {
-            Ok(match_found) => {
-                if match_found {
-                    let mut colored_line: String = line.to_string().clone();
-                    let indexes = get_indexes_f(&search_line, &pattern_to_search);
-                    for (index,end) in indexes {
-                        let colored_pattern = line[index..end].color(color.clone()).to_string();
-                        if args.bold {
-                            colored_line = colored_line.replace(&line[index..end], &colored_pattern.bold().to_string());
-                        }
-                        if args.underline {
-                            colored_line = colored_line.replace(&line[index..end], &colored_pattern.underline().to_string());
-                        }
-                        if args.italic {
-                            colored_line = colored_line.replace(&line[index..end], &colored_pattern.italic().to_string());
-                        }
-                        if args.strike {
-                            colored_line = colored_line.replace(&line[index..end], &colored_pattern.strikethrough().to_string());
-                        }
-                    }
-                    if args.line_numbers {
-                        colored_line = format!("{}: {}", index+1, colored_line);
-                    }
+    fn is_match(&self, line: &str) -> bool {
+        // like find, without collecting the indexes, for the modes that only need a yes or no
+        //   (the matches are counted by find with min_matches)
+        if self.min_matches > 1 {
+            return self.find(line).is_some();
+        }
+        let is_match = |matcher: &Matcher| matcher.is_match(line)
+            .unwrap_or_else(|error| exit_with(&error, &matcher.options().pattern));
+        let found = if self.all {
+            self.searches.iter().all(is_match)
+        } else {
+            self.searches.iter().any(is_match)
+        };
+        found && !self.exclusions.iter().any(is_match)
+    }
+}
 
-                    // Before lines, args.before is the number of lines to print before the match, default 0
-                    if args.before > 0 {
-                        if index >= 1 {
-                            let mut before_indexes = vec![];
-                            for i in 1..=args.before {
-                                if index < i {
-                                    break;
-                                }
-                                let tmp_index = index - i;
-
-                                before_indexes.push(tmp_index);
-                            }
-                            before_indexes.reverse();
-                            for i in before_indexes {
-                                let before_line = content.lines().nth(i).unwrap();
-                                println!("{}", before_line);
-                            }
-                        }
-                    }
+fn compile(options: Vec<SearchOptions>) -> Vec<Matcher> {
+    options.iter().map(|opts| match Matcher::new(opts) {
+        Ok(matcher) => matcher,
+        Err(error) => exit_with(&error, &opts.pattern),
+    }).collect()
+}
 
-                    if !args.section{
-                        println!("{}", colored_line);
-                    }
+fn compile_patterns(args: &SearchArgs) -> Patterns {
+    // compile every pattern once, before reading anything: an invalid regex exits 2 right away
+    Patterns {
+        searches: compile(search_options(args, &args.patterns)),
+        exclusions: compile(search_options(args, &args.and_not)),
+        all: args.all,
+        min_matches: args.min_matches_per_line.map_or(1, |min| min as usize),
+    }
+}
 
-                    // After lines, args.after is the number of lines to print after the match, default 0
-                    if args.after > 0 {
-                        for i in 1..=args.after {
-                            if index + i < content.lines().count() {
-                                let after_line = content.lines().nth(index + i).unwrap();
-                                println!("{}", after_line);
-                            }
-                        }
-                    }
+fn compile_capture_regexes(args: &SearchArgs) -> Vec<Regex> {
+    // the regexes whose capture groups are used by replace and extract, the Matchers
+    //   only give the spans of the matches
+    args.patterns.iter().map(|pattern| {
+        let pattern = if args.word { word_regex(pattern, args.word_ascii) } else { pattern.clone() };
+        match RegexBuilder::new(&pattern).case_insensitive(is_insensitive(args, &pattern)).build() {
+            Ok(re) => re,
+            Err(error) => exit_with(&error.into(), &pattern),
+        }
+    }).collect()
+}
 
-                    // Section, if true print the section.
-                    //   Section is the same indentation or more of the line where the pattern is found.
-                    //   Section can start before the finding
-                    if args.section {
-                        // init indentation
-                        let starting_indentation = indentation(&line, args.tabs_c);
-                        sections_to_print.push(index);
-                        // keep the index where pattern is found along with colored string
-                        found_rows.push((index, colored_line));
-                        
-                        // scroll backwards indexes, break if indentation is <= starting_indentation
-                        for i in (0..index).rev() {
-                            let tmp_line = content.lines().nth(i).unwrap();
-                            let tmp_indentation = indentation(&tmp_line, args.tabs_c);
-                            if tmp_indentation < starting_indentation {
-                                // if indentation is <= starting_indentation, break
-                                //   get the index as head of the section
-                                sections_to_print.push(i);
-                                break;
-                            }
-                            sections_to_print.push(i);
-                        }
-
-                        // scroll forward indexes, break if indentation is < starting_indentation
-                        for i in index..content.lines().count() {
-                            let tmp_line = content.lines().nth(i).unwrap();
-                            let tmp_indentation = indentation(&tmp_line, args.tabs_c);
-                            if tmp_indentation < starting_indentation {
-                                // if indentation is < starting_indentation, break
-                                break;
-                            }
-                            sections_to_print.push(i);
-                        }
+fn exit_with(error: &MyGrepError, pattern: &str) -> ! {
+    // exit code 2 is an invalid regex (or range), anything else a generic error
+    if let MyGrepError::InvalidRange(_) = error {
+        eprintln!("{}", error.to_string().color("red").bold());
+    } else if error.exit_code() == 2 {
+        let error_message = "Pattern is not a valid regex: ".color("red").bold().to_string();
+        eprintln!("{error_message} {}", pattern.color("magenta").bold());
+    } else {
+        eprintln!("{}", error.to_string().color("red").bold());
+    }
+    process::exit(error.exit_code());
+}
 
-                    }
+fn find_patterns(line: &str, searches: &[Matcher], all: bool) -> Option<Vec<Span>> {
+    // the sorted (start,end) indexes of every pattern found in the line, None if the line
+    //   doesn't match: any of the patterns is enough, unless all of them are required
+    let mut indexes = vec![];
+    let mut found_any = false;
+    for matcher in searches {
+        match matcher.match_line(line) {
+            Ok(Some(found)) => {
+                found_any = true;
+                indexes.extend(found);
+            }
+            Ok(None) => {
+                if all {
+                    return None;
                 }
-
-            },
-            Err(_) => {
-                let error_message = "Pattern is not a valid regex: ".color("red").bold().to_string();
-                eprintln!("{error_message} {}", pattern_to_search.color("magenta").bold());
-                process::exit(2);
             }
+            Err(error) => exit_with(&error, &matcher.options().pattern),
         }
     }
+    if !found_any {
+        return None;
+    }
+    indexes.sort();
+    Some(indexes)
+}
 
-    if args.section{
-        // sections_to_print is a list olf indexes to print, but are unordered and maybe duplicate.
-        //   we need to sort and remove duplicates
-        sections_to_print.sort();
-        sections_to_print.dedup();
+fn select(found: Option<Vec<Span>>, invert: bool) -> Option<Vec<Span>> {
+    // the indexes to highlight if the line is selected: with invert the lines
+    //   without the pattern are selected, and there is nothing to highlight
+    match (found, invert) {
+        (Some(indexes), false) => Some(indexes),
+        (None, true) => Some(vec![]),
+        _ => None,
+    }
+}
 
-        // print all indexes in sections_to_print
-        for index in sections_to_print {
-            // if index in found_rows, print the colored line instead of the normal line
-            if found_rows.iter().any(|x| x.0 == index) {
-                let colored_line = found_rows.iter().find(|&x| x.0 == index).unwrap().1.clone();
-                println!("{}", colored_line);
-                continue;
+fn highlight(line: &str, indexes: &[(usize, usize)], args: &SearchArgs) -> String {
+    highlight_with(line, indexes, args, |text, nth| style_pattern_in(text, match_color(args, nth), args), |text| tint(text, args))
+}
+
+fn highlight_html(line: &str, indexes: &[(usize, usize)], args: &SearchArgs) -> String {
+    highlight_with(line, indexes, args, |text, nth| html_pattern(text, match_color(args, nth), args), |text| match args.color_line {
+        Some(color) if !text.is_empty() => format!("<span style=\"color:{}\">{}</span>", css_color(color), html_escape(text)),
+        _ => html_escape(text),
+    })
+}
+
+fn highlight_with(
+    line: &str,
+    indexes: &[(usize, usize)],
+    args: &SearchArgs,
+    style: impl Fn(&str, usize) -> String,
+    tint: impl Fn(&str) -> String,
+) -> String {
+    // style the found patterns of a line (only the first with first_match_only, or the first N
+    //   with max_matches_per_line), leaving the rest untouched or tinted with color_line;
+    //   overlapping matches (of different patterns, or with overlapping) are styled as one,
+    //   in the color of the one starting first
+    let mut highlighted = String::new();
+    let mut last = 0;
+    let limit = if args.first_match_only { 1 } else { args.max_matches_per_line.unwrap_or(usize::MAX) };
+    for (nth, (start, end)) in merge_spans(indexes).into_iter().take(limit).enumerate() {
+        highlighted.push_str(&tint(&line[last..start]));
+        highlighted.push_str(&style(&line[start..end], nth));
+        last = end;
+    }
+    highlighted.push_str(&tint(&line[last..]));
+    highlighted
+}
+
+fn html_escape(text: &str) -> String {
+    // the text as it is in a page, never markup
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn css_color(color: Colors) -> &'static str {
+    // the colors of a common terminal theme
+    match color {
+        Colors::Red => "#cd3131",
+        Colors::Green => "#0dbc79",
+        Colors::Blue => "#2472c8",
+        Colors::Yellow => "#e5e510",
+        Colors::Magenta => "#bc3fbc",
+        Colors::Cyan => "#11a8cd",
+        Colors::White => "#e5e5e5",
+        Colors::Black => "#000000",
+        Colors::BrightRed => "#f14c4c",
+        Colors::BrightGreen => "#23d18b",
+        Colors::BrightBlue => "#3b8eea",
+        Colors::BrightYellow => "#f5f543",
+        Colors::BrightMagenta => "#d670d6",
+        Colors::BrightCyan => "#29b8db",
+        Colors::BrightWhite => "#ffffff",
+    }
+}
+
+fn html_pattern(pattern: &str, color: Colors, args: &SearchArgs) -> String {
+    // a found pattern as a <span> with the style of style_pattern_in
+    let mut style = format!("color:{}", css_color(color));
+    if args.bold {
+        style.push_str(";font-weight:bold");
+    }
+    let lines: Vec<&str> = [(args.underline, "underline"), (args.strike, "line-through")].iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, line)| *line)
+        .collect();
+    if !lines.is_empty() {
+        style.push_str(&format!(";text-decoration:{}", lines.join(" ")));
+    }
+    if args.italic {
+        style.push_str(";font-style:italic");
+    }
+    format!("<span style=\"{}\">{}</span>", style, html_escape(pattern))
+}
+
+fn tint(text: &str, args: &SearchArgs) -> String {
+    // the text of a matching line around the found patterns, in the color of color_line
+    //   each piece is colored on its own, the reset after a pattern would end a single tint
+    match args.color_line {
+        Some(color) if !text.is_empty() => text.color(format!("{:?}", color)).to_string(),
+        _ => text.to_string(),
+    }
+}
+
+fn walk(root: &Path, messages: bool) -> Vec<PathBuf> {
+    // all the files under root, sorted, unreadable directories are reported (with messages) and skipped
+    //   symlinked directories are listed but not followed, to avoid loops
+    if !root.is_dir() {
+        return vec![root.to_path_buf()];
+    }
+    let mut entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).collect::<Vec<_>>(),
+        Err(error) => {
+            if messages {
+                eprintln!("could not read directory `{}`: {}", root.display(), error);
             }
+            return vec![];
+        }
+    };
+    entries.sort_by_key(|entry| entry.path());
+    let mut paths = vec![];
+    for entry in entries {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            paths.extend(walk(&entry.path(), messages));
+        } else {
+            paths.push(entry.path());
+        }
+    }
+    paths
+}
+
+fn field_separator(args: &SearchArgs, is_match: bool) -> &str {
+    // what follows the line number and the byte offset: field_separator, or ": " and with
+    //   context_indent "- " for the context lines, like grep
+    match (&args.field_separator, args.context_indent, is_match) {
+        (Some(separator), _, _) => separator,
+        (None, true, false) => "- ",
+        (None, _, _) => ": ",
+    }
+}
+
+fn line_prefix(line_number: usize, is_match: bool, args: &SearchArgs, width: usize) -> String {
+    // "7: " or, with the gutter, a dimmed "  7 │ ", empty without line numbers
+    //   with context_indent the separator is ':' for matches and '-' for context lines,
+    //   like grep: "7:", "6-", or just ": " and "- " without line numbers
+    let separator = match (args.context_indent, is_match) {
+        (false, _) => "",
+        (true, true) => ":",
+        (true, false) => "-",
+    };
+    if !args.line_numbers {
+        if args.context_indent {
+            return field_separator(args, is_match).to_string();
+        }
+        return String::new();
+    }
+    if args.gutter {
+        let gutter = if args.ascii { "|" } else { "│" };
+        return format!("{}{}{} ", style_line_number(&format!("{:>width$}", line_number), args), if args.context_indent { separator } else { " " }, gutter);
+    }
+    format!("{}{}", style_line_number(&line_number.to_string(), args), field_separator(args, is_match))
+}
+
+fn style_line_number(number: &str, args: &SearchArgs) -> String {
+    // a line number in line_number_color, or dimmed so the matches stand out
+    match args.line_number_color {
+        Some(color) => number.color(format!("{:?}", color)).to_string(),
+        None => number.dimmed().to_string(),
+    }
+}
 
-            let line = content.lines().nth(index).unwrap();
-            if args.line_numbers {
-                println!("{}: {}", index+1, line);
+fn wrap_styled(text: &str, width: usize) -> String {
+    // break the text every width visible chars, the escape codes don't take room: at each break
+    //   the styles are reset and then turned on again, so a match split in two stays colored
+    let mut wrapped = String::new();
+    let mut styles = String::new();
+    let mut column = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            let mut code = String::from(c);
+            for c in chars.by_ref() {
+                code.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            if code == "\x1b[0m" {
+                styles.clear();
             } else {
-                println!("{}", line);
+                styles.push_str(&code);
+            }
+            wrapped.push_str(&code);
+            continue;
+        }
+        if column == width && width > 0 {
+            if !styles.is_empty() {
+                wrapped.push_str("\x1b[0m");
+            }
+            wrapped.push('\n');
+            wrapped.push_str(&styles);
+            column = 0;
+        }
+        wrapped.push(c);
+        column += 1;
+    }
+    wrapped
+}
+
+/// Where the results go, stdout or the output file.
+struct Output {
+    writer: Box<dyn Write>,
+    line_buffered: bool,
+    // something was printed already, the next file's context is then split from it
+    written: bool,
+    // the bytes printed and the most that can be, with max_output_bytes
+    bytes: usize,
+    max_bytes: Option<usize>,
+    truncated: bool,
+    // the terminal width, with wrap
+    wrap_width: Option<usize>,
+}
+
+impl Output {
+    fn new(args: &SearchArgs) -> Result<Output> {
+        // the output file is created, or truncated if it exists
+        let writer: Box<dyn Write> = match &args.output {
+            Some(path) => Box::new(io::BufWriter::new(
+                File::create(path).with_context(|| format!("could not create file `{}`", path.display()))?,
+            )),
+            None => Box::new(io::stdout()),
+        };
+        // wrap only in a terminal, a pipe or a file gets the lines as they are
+        let wrap_width = match terminal_size::terminal_size() {
+            Some((terminal_size::Width(width), _)) if args.wrap && args.output.is_none() && io::stdout().is_terminal() => {
+                Some(width as usize)
+            }
+            _ => None,
+        };
+        Ok(Output {
+            writer,
+            line_buffered: args.line_buffered,
+            written: false,
+            bytes: 0,
+            max_bytes: args.max_output_bytes,
+            truncated: false,
+            wrap_width,
+        })
+    }
+
+    fn line(&mut self, text: std::fmt::Arguments) {
+        match self.wrap_width {
+            Some(width) => self.terminated(format_args!("{}", wrap_styled(&text.to_string(), width)), '\n'),
+            None => self.terminated(text, '\n'),
+        }
+    }
+
+    fn file_name(&mut self, name: std::fmt::Arguments, null: bool) {
+        // with null a file name ends with NUL, a name containing a newline stays in one piece
+        self.terminated(name, if null { '\0' } else { '\n' });
+    }
+
+    fn terminated(&mut self, text: std::fmt::Arguments, terminator: char) {
+        // a closed pipe (e.g. `| head`) just ends the search, other errors are reported
+        //   with line_buffered every line is flushed right away, for a pipeline reading as it goes
+        //   past max_bytes nothing more is printed, a line that doesn't fit is dropped whole
+        if self.truncated {
+            return;
+        }
+        let text = format!("{}{}", text, terminator);
+        if self.max_bytes.is_some_and(|max_bytes| self.bytes + text.len() > max_bytes) {
+            self.truncated = true;
+            eprintln!("{}", format!("output truncated after {}", plural(self.bytes, "byte", "bytes")).yellow());
+            return;
+        }
+        self.bytes += text.len();
+        self.written = true;
+        let mut written = self.writer.write_all(text.as_bytes());
+        if self.line_buffered && written.is_ok() {
+            written = self.writer.flush();
+        }
+        if let Err(error) = written {
+            if error.kind() == io::ErrorKind::BrokenPipe {
+                process::exit(0);
             }
+            eprintln!("{}", format!("could not write the output: {}", error).color("red").bold());
+            process::exit(1);
         }
     }
 
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context("could not write the output")
+    }
+}
+
+/// A piece of a format template, text to copy or a placeholder to expand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FormatPart {
+    Literal(String),
+    File,
+    Line,
+    Col,
+    VisualCol,
+    Match,
+    Text,
+}
+
+fn parse_format(template: &str) -> Result<Vec<FormatPart>, String> {
+    // split the template in text and placeholders, `{{` and `}}` are literal braces
+    let mut parts = vec![];
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err(format!("unclosed placeholder `{{{}` in format", name)),
+                    }
+                }
+                let part = match name.as_str() {
+                    "file" => FormatPart::File,
+                    "line" => FormatPart::Line,
+                    "col" => FormatPart::Col,
+                    "vcol" => FormatPart::VisualCol,
+                    "match" => FormatPart::Match,
+                    "text" => FormatPart::Text,
+                    _ => return Err(format!(
+                        "unknown placeholder `{{{}}}` in format, use {{file}}, {{line}}, {{col}}, {{vcol}}, {{match}} or {{text}}",
+                        name
+                    )),
+                };
+                if !text.is_empty() {
+                    parts.push(FormatPart::Literal(std::mem::take(&mut text)));
+                }
+                parts.push(part);
+            }
+            '}' => return Err("unmatched `}` in format, use `}}` for a literal brace".to_string()),
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(FormatPart::Literal(text));
+    }
+    Ok(parts)
+}
+
+fn expand_format(parts: &[FormatPart], file: &str, line_number: usize, start: usize, line: &str, found: &str, tabs_c: usize) -> String {
+    // the template for one found pattern, col is the 1-based byte column of the match
+    //   and vcol the one seen in an editor, with tabs of tabs_c columns
+    let mut expanded = String::new();
+    for part in parts {
+        match part {
+            FormatPart::Literal(text) => expanded.push_str(text),
+            FormatPart::File => expanded.push_str(file),
+            FormatPart::Line => expanded.push_str(&line_number.to_string()),
+            FormatPart::Col => expanded.push_str(&(start + 1).to_string()),
+            FormatPart::VisualCol => expanded.push_str(&visual_column(line, start, tabs_c).to_string()),
+            FormatPart::Match => expanded.push_str(found),
+            FormatPart::Text => expanded.push_str(line),
+        }
+    }
+    expanded
+}
+
+fn interpret_escapes(pattern: &str) -> Result<String, String> {
+    // like printf: \t \n \r \0 \\ and \xNN (the char U+00NN), any other backslash is kept
+    let mut interpreted = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            interpreted.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => interpreted.push('\t'),
+            Some('n') => interpreted.push('\n'),
+            Some('r') => interpreted.push('\r'),
+            Some('0') => interpreted.push('\0'),
+            Some('\\') => interpreted.push('\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let code = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape `\\x{}`, expected two hex digits", hex))?;
+                interpreted.push(char::from(code));
+            }
+            Some(other) => {
+                interpreted.push('\\');
+                interpreted.push(other);
+            }
+            None => interpreted.push('\\'),
+        }
+    }
+    Ok(interpreted)
+}
+
+fn parse_line_range(range: &str) -> Result<(usize, usize), String> {
+    // "100:200", 1-based and inclusive, "100:" goes to the end and ":200" starts from the first line
+    let Some((start, end)) = range.split_once(':') else {
+        return Err("expected START:END, like 100:200".to_string());
+    };
+    let start = if start.is_empty() { 1 } else { start.parse().map_err(|_| format!("invalid start line `{}`", start))? };
+    let end = if end.is_empty() { usize::MAX } else { end.parse().map_err(|_| format!("invalid end line `{}`", end))? };
+    if start == 0 || start > end {
+        return Err("lines are 1-based and START can't be after END".to_string());
+    }
+    Ok((start, end))
+}
+
+fn parse_window(window: &str) -> Result<(usize, usize), String> {
+    // "2:5", both sides are needed, 0 for none
+    let Some((before, after)) = window.split_once(':') else {
+        return Err("expected BEFORE:AFTER, like 2:5".to_string());
+    };
+    let before = before.parse().map_err(|_| format!("invalid number of lines before `{}`", before))?;
+    let after = after.parse().map_err(|_| format!("invalid number of lines after `{}`", after))?;
+    Ok((before, after))
+}
+
+fn parse_size(size: &str) -> Result<u64, String> {
+    // "4096", "512K", "100M" or "2G", the suffixes are powers of 1024 (lowercase works too)
+    let (number, unit) = match size.char_indices().last() {
+        Some((last, c)) if c.is_ascii_alphabetic() => (&size[..last], c.to_ascii_uppercase()),
+        _ => (size, 'B'),
+    };
+    let scale: u64 = match unit {
+        'B' => 1,
+        'K' => 1 << 10,
+        'M' => 1 << 20,
+        'G' => 1 << 30,
+        _ => return Err(format!("unknown unit `{}`, expected K, M or G", unit)),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("invalid size `{}`, expected a number like 512M", size))?;
+    number.checked_mul(scale).ok_or_else(|| format!("the size `{}` is too big", size))
+}
+
+fn in_line_range(args: &SearchArgs, line_number: usize) -> bool {
+    match args.lines {
+        Some((start, end)) => start <= line_number && line_number <= end,
+        None => true,
+    }
+}
+
+fn bracket(line: &str, args: &SearchArgs) -> String {
+    // a matching line between pre and post, as it is without them
+    format!("{}{}{}", args.pre.as_deref().unwrap_or(""), line, args.post.as_deref().unwrap_or(""))
+}
+
+fn parse_time(time: &str, format: &str) -> Option<NaiveDateTime> {
+    // a time in the format, or a date (at midnight)
+    NaiveDateTime::parse_from_str(time, format).ok()
+        .or_else(|| NaiveDate::parse_from_str(time, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+fn line_time(line: &str, format: &str) -> Option<NaiveDateTime> {
+    // the timestamp at the start of the line, a format without the time is a date at midnight
+    NaiveDateTime::parse_and_remainder(line, format).map(|(time, _)| time).ok()
+        .or_else(|| NaiveDate::parse_and_remainder(line, format).ok().and_then(|(date, _)| date.and_hms_opt(0, 0, 0)))
+}
+
+fn in_time_window(args: &SearchArgs, line: &str) -> bool {
+    // with since or until, a line stamped in the window, or without a stamp with keep_untimed
+    let Some((since, until)) = args.time_window else {
+        return true;
+    };
+    match line_time(line, &args.time_format) {
+        Some(time) => since <= time && time <= until,
+        None => args.keep_untimed,
+    }
+}
+
+fn json_text<'a>(args: &SearchArgs, line: &'a str) -> Result<Option<Cow<'a, str>>, serde_json::Error> {
+    // the text searched in a line: the line, or with json_field the value at the path (None without it),
+    //   an array is indexed by number
+    let Some(field) = &args.json_field else {
+        return Ok(Some(Cow::Borrowed(line)));
+    };
+    let parsed: serde_json::Value = serde_json::from_str(line)?;
+    let mut value = &parsed;
+    for key in field.split('.') {
+        let next = match value {
+            serde_json::Value::Array(items) => key.parse::<usize>().ok().and_then(|index| items.get(index)),
+            _ => value.get(key),
+        };
+        let Some(next) = next else {
+            return Ok(None);
+        };
+        value = next;
+    }
+    Ok(Some(Cow::Owned(match value {
+        serde_json::Value::String(text) => text.clone(),
+        other => other.to_string(),
+    })))
+}
+
+fn searched_text<'a>(args: &SearchArgs, path: Option<&Path>, line_number: usize, line: &'a str) -> Option<Cow<'a, str>> {
+    // like json_text, a line that is not JSON is reported and skipped
+    match json_text(args, line) {
+        Ok(text) => text,
+        Err(error) => {
+            warn(args, format_args!("{}: line {} is not JSON, it is skipped: {}", display_name(path), line_number, error));
+            None
+        }
+    }
+}
+
+fn skipped_line(args: &SearchArgs, line: &str) -> bool {
+    // a line that is never searched: a blank one with ignore_blank, one out of since and until,
+    //   or one longer than max_line_length
+    ignored_blank(args, line) || too_long(args, line) || !in_time_window(args, line)
+}
+
+fn too_long(args: &SearchArgs, line: &str) -> bool {
+    args.max_line_length.is_some_and(|max| line.len() as u64 > max)
+}
+
+fn ignored_blank(args: &SearchArgs, line: &str) -> bool {
+    // with ignore_blank a blank line is as if it were not there
+    args.ignore_blank && line.trim().is_empty()
+}
+
+fn past_line_range(args: &SearchArgs, line_number: usize) -> bool {
+    args.lines.is_some_and(|(_, end)| line_number > end)
+}
+
+fn open_input(path: Option<&Path>) -> Result<Box<dyn BufRead>> {
+    // the file, or stdin for None, to be read line by line
+    Ok(match path {
+        Some(path) => Box::new(io::BufReader::new(
+            File::open(path).with_context(|| format!("could not read file `{}`", path.display()))?,
+        )),
+        None => Box::new(io::stdin().lock()),
+    })
+}
+
+fn run_command(command: &str) -> Result<(Vec<u8>, process::ExitStatus)> {
+    // the stdout of the command, run by the shell, its stderr goes to ours untouched
+    let mut shell = if cfg!(windows) {
+        let mut shell = process::Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = process::Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let output = shell.arg(command)
+        .stderr(process::Stdio::inherit())
+        .output()
+        .with_context(|| format!("could not run the command `{}`", command))?;
+    Ok((output.stdout, output.status))
+}
+
+fn diff_file_name(header: &str) -> String {
+    // the path of a `+++ b/src/main.rs` header, without the b/ of git and the date of diff -u
+    let name = header.split('\t').next().unwrap_or(header).trim_end();
+    name.strip_prefix("b/").unwrap_or(name).to_string()
+}
+
+fn parse_hunk_header(line: &str) -> Option<(usize, usize, usize)> {
+    // the first line in the new file and the lines on each side of `@@ -12,5 +14,7 @@`,
+    //   a side without its count has a single line
+    let mut sides = line.strip_prefix("@@ -")?.split_whitespace();
+    let side = |side: &str| -> Option<(usize, usize)> {
+        match side.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((side.parse().ok()?, 1)),
+        }
+    };
+    let (_, old_count) = side(sides.next()?)?;
+    let (new_start, new_count) = side(sides.next()?.strip_prefix('+')?)?;
+    Some((new_start, old_count, new_count))
+}
+
+fn split_records<'a>(content: &'a str, args: &SearchArgs) -> Vec<&'a str> {
+    // the lines, or with record_delimiter the records between the delimiters, without the newline
+    //   that ends the input (a trailing delimiter doesn't start an empty record either, like the last newline)
+    let Some(delimiter) = args.record_delimiter.as_deref() else {
+        return content.lines().collect();
+    };
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    let mut records: Vec<&str> = content.split(delimiter).collect();
+    if records.last() == Some(&"") {
+        records.pop();
+    }
+    records
+}
+
+fn first_match(mut reader: Box<dyn BufRead + '_>, path: Option<&Path>, patterns: &Patterns, args: &SearchArgs, scanned_lines: &mut usize) -> Result<bool> {
+    // read the input line by line until the first selected line (the min_count-th one), the rest is never read
+    //   the records of record_delimiter are split once the input is read whole
+    let lines: Box<dyn Iterator<Item = Result<String>>> = match args.record_delimiter {
+        Some(_) => {
+            let mut content = vec![];
+            reader.read_to_end(&mut content).context("could not read the input")?;
+            let content = decode(content, args.text).context("could not read the input")?;
+            let records: Vec<String> = split_records(&without_ansi(args, without_bom(args, &content)), args).into_iter().map(str::to_string).collect();
+            Box::new(records.into_iter().map(Ok))
+        }
+        None => Box::new(reader.split(b'\n').map(|line| {
+            let mut line = decode(line.context("could not read the input")?, args.text).context("could not read the input")?;
+            if line.ends_with('\r') {
+                line.pop();
+            }
+            Ok(line)
+        })),
+    };
+    let needed = args.min_count.unwrap_or(1);
+    let mut found: u64 = 0;
+    for (index, line) in lines.enumerate() {
+        if past_line_range(args, index+1) {
+            break;
+        }
+        let line = line?;
+        let line = without_ansi(args, if index == 0 { without_bom(args, &line) } else { &line });
+        if !in_line_range(args, index+1) || skipped_line(args, &line) {
+            continue;
+        }
+        let Some(line) = searched_text(args, path, index+1, &line) else {
+            continue;
+        };
+        *scanned_lines += 1;
+        if patterns.is_match(&line) != args.invert {
+            found += 1;
+            if found == needed {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+fn path_selected(args: &SearchArgs, path: &Path) -> bool {
+    // with path_contains, only the paths containing the text, case insensitive like the patterns
+    let Some(text) = &args.path_contains else {
+        return true;
+    };
+    let path = path.display().to_string();
+    if is_insensitive(args, text) {
+        path.to_lowercase().contains(&text.to_lowercase())
+    } else {
+        path.contains(text.as_str())
+    }
+}
+
+fn display_name(path: Option<&Path>) -> String {
+    // the name of an input in the output, - for stdin
+    match path {
+        Some(path) => path.display().to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn read_file_list(list: &Path, null: bool) -> Result<Vec<PathBuf>> {
+    // the paths of files_from, one per line (or NUL separated), - reads them from stdin
+    let mut content = String::new();
+    if list == Path::new("-") {
+        io::stdin().lock().read_to_string(&mut content).context("could not read the list of files from stdin")?;
+    } else {
+        content = std::fs::read_to_string(list)
+            .with_context(|| format!("could not read the list of files `{}`", list.display()))?;
+    }
+    let paths: Vec<PathBuf> = if null {
+        content.split('\0').filter(|path| !path.is_empty()).map(PathBuf::from).collect()
+    } else {
+        content.lines().filter(|path| !path.is_empty()).map(PathBuf::from).collect()
+    };
+    Ok(paths)
+}
+
+fn warn(args: &SearchArgs, message: std::fmt::Arguments) {
+    // a problem that doesn't stop the search, to stderr unless no_messages
+    if !args.no_messages {
+        eprintln!("{}", message.to_string().yellow());
+    }
+}
+
+fn print_count(args: &SearchArgs, out: &mut Output, path: Option<&Path>, matched_lines: usize, pattern_counts: &[usize]) {
+    // with count, the number of matching lines, named `file: 3` for the files of files_from
+    //   and the members of archive, or with per_pattern `foo:12 bar:3`
+    //   (sort_matches counts the found patterns instead)
+    if !args.count || args.sort_matches {
+        return;
+    }
+    let count = match args.per_pattern {
+        true => args.patterns.iter().zip(pattern_counts)
+            .map(|(pattern, count)| format!("{}:{}", pattern, count))
+            .collect::<Vec<String>>()
+            .join(" "),
+        false => matched_lines.to_string(),
+    };
+    // of several files the ones without a match are left out, unless include_zero
+    if args.several_inputs && matched_lines == 0 && !args.include_zero {
+        return;
+    }
+    if args.several_inputs {
+        out.line(format_args!("{}: {}", display_name(path), count));
+    } else {
+        out.line(format_args!("{}", count));
+    }
+}
+
+fn print_sorted_matches(args: &SearchArgs, out: &mut Output, mut found_patterns: Vec<String>) {
+    // with sort_matches, the found patterns sorted, or with count once each after the number
+    //   of times they were found, like `sort | uniq -c`
+    found_patterns.sort();
+    if !args.count {
+        for found_pattern in &found_patterns {
+            out.line(format_args!("{}", style_pattern(found_pattern, args)));
+        }
+        return;
+    }
+    let mut counted: Vec<(usize, &str)> = vec![];
+    for found_pattern in &found_patterns {
+        match counted.last_mut() {
+            Some((count, text)) if *text == found_pattern.as_str() => *count += 1,
+            _ => counted.push((1, found_pattern)),
+        }
+    }
+    let width = counted.iter().map(|(count, _)| count.to_string().len()).max().unwrap_or(0);
+    for (count, text) in counted {
+        out.line(format_args!("{:>width$} {}", count, style_pattern(text, args)));
+    }
+}
+
+fn count_keys(re: &Regex, line: &str, counted_keys: &mut HashMap<String, usize>) {
+    // count_by, every match of the regex in the line adds one to its key: the first group,
+    //   the whole match without groups (a match where the group is not found isn't counted)
+    for caps in re.captures_iter(line) {
+        if let Some(key) = caps.get(1).or_else(|| (re.captures_len() == 1).then(|| caps.get(0)).flatten()) {
+            *counted_keys.entry(key.as_str().to_string()).or_insert(0) += 1;
+        }
+    }
+}
+
+fn print_counted_keys(args: &SearchArgs, out: &mut Output, counted_keys: HashMap<String, usize>) {
+    // with count_by, `key: count` the most common first, the keys found as often sorted by name
+    let mut counted: Vec<(String, usize)> = counted_keys.into_iter().collect();
+    counted.sort_by(|(key, count), (other_key, other_count)| other_count.cmp(count).then_with(|| key.cmp(other_key)));
+    for (key, count) in counted {
+        out.line(format_args!("{}: {}", style_pattern(&key, args), count));
+    }
+}
+
+fn print_heading(args: &SearchArgs, out: &mut Output, path: Option<&Path>) {
+    // the first match of a file, the context of each file stays its own: with after or before
+    //   the lines of the previous file are split from this one by --, like grep's groups
+    if (args.after > 0 || args.before > 0) && out.written {
+        out.line(format_args!("--"));
+    }
+    // with heading, the file name once above its matches (nothing for stdin)
+    if !args.heading {
+        return;
+    }
+    if let Some(path) = path {
+        if args.html {
+            out.line(format_args!("<b>{}</b>", html_escape(&path.display().to_string())));
+            return;
+        }
+        out.file_name(format_args!("{}", path.display().to_string().color(format!("{:?}", args.filename_color)).bold()), args.null);
+    }
+}
+
+fn print_summary(args: &SearchArgs, matched_lines: usize, files_matched: usize, lines_scanned: usize) {
+    // with summary, "3 matches in 1 file (5 lines scanned)" to stderr
+    if !args.summary {
+        return;
+    }
+    eprintln!(
+        "{} in {} ({} scanned)",
+        plural(matched_lines, "match", "matches"),
+        plural(files_matched, "file", "files"),
+        plural(lines_scanned, "line", "lines"),
+    );
+}
+
+fn print_regex_hint(args: &SearchArgs, matched_lines: usize) {
+    // nothing found with a literal pattern that looks like a regex (`a.b`, `foo*`), suggest -R
+    if matched_lines > 0 || args.regex || args.pcre || args.numeric_range || args.fuzzy.is_some() || args.no_messages {
+        return;
+    }
+    let metachars = ".*+?[](){}|^$";
+    if args.patterns.iter().any(|pattern| pattern.chars().any(|c| metachars.contains(c))) {
+        eprintln!("{}", "No match for the literal pattern, use -R to search it as a regex".yellow());
+    }
+}
+
+fn print_scanned(args: &SearchArgs, scanned_lines: usize, long_lines: usize) {
+    // with scanned, "Scanned 40 lines" to stderr, the lines skipped by an early exit are not counted
+    //   with max_line_length, ", skipped 2 longer than 10000 bytes" after it
+    if !args.scanned {
+        return;
+    }
+    match args.max_line_length {
+        Some(max) => eprintln!("Scanned {}, skipped {} longer than {}", plural(scanned_lines, "line", "lines"), long_lines, plural(max as usize, "byte", "bytes")),
+        None => eprintln!("Scanned {}", plural(scanned_lines, "line", "lines")),
+    }
+}
+
+fn print_summary_by_file(args: &SearchArgs, counts: &[(String, usize)]) {
+    // with summary_by_file, "file: count" for every searched file to stderr,
+    //   the files with more matches first, then by name
+    if !args.summary_by_file {
+        return;
+    }
+    let mut counts = counts.to_vec();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    eprintln!("----------------------------");
+    for (file, count) in counts {
+        eprintln!("{}: {}", file, count);
+    }
+}
+
+fn print_heatmap(args: &SearchArgs, path: Option<&Path>, lines: usize, matched_indexes: &[usize]) {
+    // with heatmap, a bar for each of the (at most) 20 parts of the file, as long as its matching lines:
+    //   "  21-40   ######          6" with the longest bar 40 chars, to stderr
+    if !args.heatmap {
+        return;
+    }
+    eprintln!("{}: {} in {}", display_name(path), plural(matched_indexes.len(), "matching line", "matching lines"), plural(lines, "line", "lines"));
+    let buckets = lines.min(20);
+    if buckets == 0 {
+        return;
+    }
+    let mut counts = vec![0; buckets];
+    for &index in matched_indexes {
+        counts[index * buckets / lines] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+    let width = lines.to_string().len();
+    for (bucket, &count) in counts.iter().enumerate() {
+        // the 1-based lines of the bucket, from the first to the last one
+        let first = bucket * lines / buckets + 1;
+        let last = (bucket + 1) * lines / buckets;
+        let bar = "#".repeat(count * 40 / most);
+        eprintln!("{:>width$}-{:<width$} {:<40} {}", first, last, bar, count);
+    }
+}
+
+fn print_files_searched(args: &SearchArgs, files: usize) {
+    // with count_files, "Searched 3 files" to stderr, stdin counts as one file
+    if args.count_files {
+        eprintln!("Searched {}", plural(files, "file", "files"));
+    }
+}
+
+fn print_files_matched(args: &SearchArgs, files: usize) {
+    // with count_files_matched, "3 files matched" to stderr, a file counts once
+    if args.count_files_matched {
+        eprintln!("{} matched", plural(files, "file", "files"));
+    }
+}
+
+fn plural(count: usize, singular: &str, plural: &str) -> String {
+    // "1 match", "3 matches"
+    if count == 1 {
+        format!("{} {}", count, singular)
+    } else {
+        format!("{} {}", count, plural)
+    }
+}
+
+fn bench(lines: usize, runs: u32) -> Result<()> {
+    // each search is a run of this same executable, so the timings include the reading and the printing
+    //   like a real search (the output goes nowhere, it would be the terminal that is measured otherwise)
+    let path = std::env::temp_dir().join(format!("mygrep-bench-{}.log", process::id()));
+    let mut content = String::new();
+    for i in 0..lines {
+        // a log with sections: a line starting a request, then its indented details, now and then an error
+        if i % 4 == 0 {
+            content.push_str(&format!("2024-05-01 10:{:02}:{:02} INFO request {} from 10.0.{}.{}\n", i / 60 % 60, i % 60, i, i % 256, i % 7));
+        } else if i % 97 == 0 {
+            content.push_str(&format!("    ERROR could not reach db-{} after {} ms\n", i % 13, i % 1000));
+        } else {
+            content.push_str(&format!("    served /api/items/{} in {} ms\n", i % 500, i % 300));
+        }
+    }
+    std::fs::write(&path, &content).with_context(|| format!("could not write `{}`", path.display()))?;
+    let executable = std::env::current_exe().context("could not find the mygrep executable")?;
+    let searches: [(&str, &[&str]); 4] = [
+        ("literal", &["ERROR"]),
+        ("regex", &["-R", r"db-\d+ after [0-9]{3} ms"]),
+        ("insensitive", &["-I", "error"]),
+        ("section", &["-S", "ERROR"]),
+    ];
+    println!("{} lines, {:.1} MB, best of {}", lines, content.len() as f64 / (1024.0 * 1024.0), plural(runs as usize, "run", "runs"));
+    let result = searches.iter().try_for_each(|(name, search)| {
+        let mut best = std::time::Duration::MAX;
+        for _ in 0..runs {
+            let start = std::time::Instant::now();
+            let status = process::Command::new(&executable)
+                .arg("search")
+                .args(*search)
+                .arg(&path)
+                .stdout(process::Stdio::null())
+                .stderr(process::Stdio::null())
+                .status()
+                .context("could not run the search")?;
+            // 1 is no match, anything else is an error of the search itself
+            if !matches!(status.code(), Some(0 | 1)) {
+                anyhow::bail!("the {} search failed, {}", name, status);
+            }
+            best = best.min(start.elapsed());
+        }
+        println!("{:<12} {:>9.2} ms", name, best.as_secs_f64() * 1000.0);
+        Ok(())
+    });
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+fn print_config() {
+    // print the options of the search command as `option = default`
+    let command = Cli::command();
+    let Some(search) = command.find_subcommand("search") else {
+        return;
+    };
+    for arg in search.get_arguments() {
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        let default: Vec<String> = arg.get_default_values().iter()
+            .map(|value| value.to_string_lossy().to_string())
+            .collect();
+        println!("{} = {}", long, default.join(","));
+    }
+}
+
+fn is_zip(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+fn read_archive(path: &Path, text: bool) -> Result<Vec<(Option<PathBuf>, String)>> {
+    // the text members of a zip, named `archive.zip:member`, in the order of the archive
+    //   the directories and the binary members (a NUL byte, or not UTF-8) are skipped, unless text
+    let file = File::open(path).with_context(|| format!("could not read file `{}`", path.display()))?;
+    let mut archive = zip::ZipArchive::new(io::BufReader::new(file))
+        .with_context(|| format!("could not read the archive `{}`", path.display()))?;
+    let mut members = vec![];
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index)
+            .with_context(|| format!("could not read the archive `{}`", path.display()))?;
+        if member.is_dir() {
+            continue;
+        }
+        let name = member.name()
+            .with_context(|| format!("could not read the archive `{}`", path.display()))?
+            .to_string();
+        let mut bytes = vec![];
+        member.read_to_end(&mut bytes)
+            .with_context(|| format!("could not read `{}` in the archive `{}`", name, path.display()))?;
+        if bytes.contains(&0) && !text {
+            continue;
+        }
+        let Ok(content) = decode(bytes, text) else {
+            continue;
+        };
+        members.push((Some(PathBuf::from(format!("{}:{}", path.display(), name))), content));
+    }
+    Ok(members)
+}
+
+fn decode(bytes: Vec<u8>, text: bool) -> Result<String, std::string::FromUtf8Error> {
+    // with text the bytes that are not UTF-8 become U+FFFD instead of an error, like grep -a
+    if text {
+        return Ok(String::from_utf8_lossy(&bytes).into_owned());
+    }
+    String::from_utf8(bytes)
+}
+
+fn without_bom<'a>(args: &SearchArgs, text: &'a str) -> &'a str {
+    // the BOM (EF BB BF) that the Windows editors put at the start of a file is not part of its
+    //   first line, unless keep_bom
+    if args.keep_bom {
+        return text;
+    }
+    text.strip_prefix('\u{feff}').unwrap_or(text)
+}
+
+fn without_ansi<'a>(args: &SearchArgs, text: &'a str) -> Cow<'a, str> {
+    // with strip_ansi the text without its escape codes, borrowed when it has none
+    if !args.strip_ansi || !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    Cow::Owned(strip_ansi_codes(text))
+}
+
+fn strip_ansi_codes(text: &str) -> String {
+    // CSI (ESC [ ... a letter: colors, cursor moves), OSC (ESC ] ... BEL or ESC \: titles, links)
+    //   and the two chars escapes (ESC and one more) are dropped, the rest is kept as it is
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                chars.by_ref().find(|c| ('\x40'..='\x7e').contains(c));
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || c == '\x1b' && chars.next_if_eq(&'\\').is_some() {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+fn read_input(path: Option<&Path>, text: bool) -> Result<String> {
+    // the whole content of the file, or of stdin for None
+    let mut content = vec![];
+    // if path is not None, do the following
+    if let Some(path) = path {
+        content = std::fs::read(path)
+            .with_context(|| format!("could not read file `{}`", path.display()))?;
+        return decode(content, text).with_context(|| format!("could not read file `{}`", path.display()));
+    }
+    // if no path is give we hope to have a stdin as content
+    //   read as it is, the lines are split on their own terminators (\n or \r\n) like for a file,
+    //   a last line without a newline doesn't get one
+    io::stdin().lock().read_to_end(&mut content).context("could not read stdin")?;
+
+    decode(content, text).context("could not read stdin")
+}
+
+fn replace_line(line: &str, capture_regexes: &[Regex], replacement: &str) -> String {
+    // the line with every match of the patterns replaced, without colors
+    let mut replaced = line.to_string();
+    for re in capture_regexes {
+        replaced = re.replace_all(&replaced, |caps: &regex::Captures| expand_replacement(caps, replacement)).to_string();
+    }
+    replaced
+}
+
+fn write_replaced(args: &SearchArgs, out: &mut Output, patterns: &Patterns, capture_regexes: &[Regex], root: &Path, output_dir: &Path) -> Result<()> {
+    // with output_dir, every file under root with a match is written under output_dir at the
+    //   same relative path (a file's own name if root is a file), with the selected lines replaced
+    //   the line terminators are kept, a file that can't be read or that exists is reported and skipped
+    let replacement = args.replace.as_deref().expect("output_dir requires replace");
+    for path in walk(root, !args.no_messages).into_iter().filter(|path| path_selected(args, path)) {
+        let content = match read_input(Some(&path), args.text) {
+            Ok(content) => content,
+            Err(error) => {
+                warn(args, format_args!("{:#}", error));
+                continue;
+            }
+        };
+        let mut changed = false;
+        let mut replaced = String::with_capacity(content.len());
+        for (index, record) in without_bom(args, &content).split_inclusive('\n').enumerate() {
+            let line = record.trim_end_matches('\n').trim_end_matches('\r');
+            if in_line_range(args, index+1) && patterns.is_match(line) {
+                changed = true;
+                replaced.push_str(&replace_line(line, capture_regexes, replacement));
+                replaced.push_str(&record[line.len()..]);
+            } else {
+                replaced.push_str(record);
+            }
+        }
+        if !changed {
+            continue;
+        }
+        let relative = match root.is_dir() {
+            true => path.strip_prefix(root).unwrap_or(&path),
+            false => Path::new(path.file_name().unwrap_or(path.as_os_str())),
+        };
+        let target = output_dir.join(relative);
+        if target.exists() && !args.overwrite {
+            warn(args, format_args!("`{}` exists, it is not replaced without overwrite", target.display()));
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent).with_context(|| format!("could not create directory `{}`", parent.display()))?;
+        }
+        std::fs::write(&target, replaced).with_context(|| format!("could not write file `{}`", target.display()))?;
+        out.file_name(format_args!("{}", target.display()), args.null);
+    }
+    Ok(())
+}
+
+fn snippets(line: &str, indexes: &[Span], radius: usize, args: &SearchArgs) -> String {
+    // the RADIUS chars around each match, highlighted, the windows that overlap are merged
+    //   a cut is marked with an ellipsis
+    let mut windows: Vec<(usize, usize, Vec<Span>)> = vec![];
+    for (start, end) in merge_spans(indexes) {
+        let before = line[..start].char_indices().rev().take(radius).last().map_or(start, |(index, _)| index);
+        let after = end + line[end..].chars().take(radius).map(char::len_utf8).sum::<usize>();
+        match windows.last_mut() {
+            Some((_, last_after, spans)) if before <= *last_after => {
+                *last_after = after;
+                spans.push((start, end));
+            }
+            _ => windows.push((before, after, vec![(start, end)])),
+        }
+    }
+    let ellipsis = if args.ascii { "..." } else { "…" };
+    windows.iter().map(|(before, after, spans)| {
+        let spans: Vec<Span> = spans.iter().map(|&(start, end)| (start - before, end - before)).collect();
+        format!(
+            "{}{}{}",
+            if *before > 0 { ellipsis } else { "" },
+            highlight(&line[*before..*after], &spans, args),
+            if *after < line.len() { ellipsis } else { "" },
+        )
+    }).collect::<Vec<String>>().join(" ")
+}
+
+/// A selected line of an input, with what goes before it (number, offset).
+struct Selected<'a> {
+    line_number: usize,
+    line: &'a str,
+    indexes: &'a [Span],
+    prefix: String,
+}
+
+fn print_match(
+    args: &SearchArgs,
+    out: &mut Output,
+    selected: &Selected,
+    file_name: &str,
+    format_parts: Option<&[FormatPart]>,
+    capture_regexes: &[Regex],
+) -> Option<String> {
+    // print a selected line the way the output mode asks, or give the colored line (prefix
+    //   included) back to be printed with its context, section or paragraph
+    let (line, indexes) = (selected.line, selected.indexes);
+
+    // Html, the line in the page, escaped and styled
+    if args.html {
+        out.line(format_args!("{}{}", html_escape(&selected.prefix), bracket(&highlight_html(line, indexes, args), args)));
+        return None;
+    }
+    let mut colored_line: String = line.to_string().clone();
+    // Replace, the line is printed with every match replaced, the replacements get the colors
+    if let Some(replacement) = &args.replace {
+        for re in capture_regexes {
+            colored_line = re.replace_all(&colored_line, |caps: &regex::Captures| {
+                style_pattern(&expand_replacement(caps, replacement), args)
+            }).to_string();
+        }
+    } else if let Some(radius) = args.snippet.filter(|_| !indexes.is_empty()) {
+        colored_line = snippets(line, indexes, radius, args);
+    } else {
+        colored_line = highlight(line, indexes, args);
+    }
+    // the column of the first match, none for an inverted line
+    let column = match indexes.first() {
+        Some(&(start, _)) => column_prefix(line, start, args),
+        None => String::new(),
+    };
+    colored_line = format!("{}{}{}", selected.prefix, column, bracket(&colored_line, args));
+
+    // Offsets only, the spans as they are, 0-based and end exclusive like a slice of the line
+    if args.offsets_only {
+        for &(start, end) in indexes {
+            out.line(format_args!("{}:{}-{}", selected.line_number, start, end));
+        }
+        return None;
+    }
+
+    // Format, print the template once for each found pattern
+    if let Some(format_parts) = format_parts {
+        for &(start, end) in indexes {
+            out.line(format_args!("{}", expand_format(format_parts, file_name, selected.line_number, start, line, &line[start..end], args.tabs_c)));
+        }
+        return None;
+    }
+
+    // Extract, the named groups of each match, tab separated, empty when a group didn't take part
+    if let Some(names) = &args.extract {
+        let prefix = &selected.prefix;
+        for re in capture_regexes {
+            for caps in re.captures_iter(line) {
+                let columns: Vec<&str> = names.iter().map(|name| caps.name(name).map_or("", |group| group.as_str())).collect();
+                out.line(format_args!("{}{}", prefix, columns.join("\t")));
+            }
+        }
+        return None;
+    }
+
+    // Only matching, print the found patterns instead of the whole line
+    //   one per line, or joined on a single line with args.join
+    if args.only_matching {
+        let found_patterns: Vec<(usize, String)> = indexes.iter()
+            .filter(|(start, end)| start < end)
+            .enumerate()
+            .map(|(nth, &(start, end))| (start, style_pattern_in(&line[start..end], match_color(args, nth), args)))
+            .collect();
+        let prefix = &selected.prefix;
+        //   the column is the one of each match, or of the first when joined
+        match &args.join {
+            Some(separator) => {
+                let column = found_patterns.first().map_or(String::new(), |(start, _)| column_prefix(line, *start, args));
+                let joined: Vec<&str> = found_patterns.iter().map(|(_, found_pattern)| found_pattern.as_str()).collect();
+                out.line(format_args!("{}{}{}", prefix, column, joined.join(separator)));
+            }
+            None => {
+                for (start, found_pattern) in found_patterns {
+                    out.line(format_args!("{}{}{}", prefix, column_prefix(line, start, args), found_pattern));
+                }
+            }
+        }
+        return None;
+    }
+    Some(colored_line)
+}
+
+fn is_stream(path: Option<&Path>) -> bool {
+    // stdin, or a named pipe: something that is read as it is written
+    let Some(path) = path else {
+        return true;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_fifo())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+fn streamable(args: &SearchArgs) -> bool {
+    // the gutter aligns the line numbers to the widest one, known once the input is read
+    whole_input_modes(args).is_empty() && !args.gutter
+}
+
+fn whole_input_modes(args: &SearchArgs) -> Vec<&'static str> {
+    // the modes that need the lines after a match (or all of them, like min_count) read the whole input first
+    [
+        (args.section, "section"),
+        (args.paragraph, "paragraph"),
+        (args.near.is_some(), "near"),
+        (args.join_lines.is_some(), "join_lines"),
+        (args.diff, "diff"),
+        (args.last, "last"),
+        (args.min_count.is_some(), "min_count"),
+        (args.record_delimiter.is_some(), "record_delimiter"),
+    ].into_iter().filter(|(enabled, _)| *enabled).map(|(_, name)| name).collect()
+}
+
+fn over_memory_limit(args: &SearchArgs, path: &Path) -> bool {
+    // a file bigger than limit_memory, never a zip searched by archive
+    let Some(limit) = args.limit_memory else {
+        return false;
+    };
+    !(args.archive && is_zip(path)) && std::fs::metadata(path).is_ok_and(|metadata| metadata.len() > limit)
+}
+
+fn stream_input(
+    args: &SearchArgs,
+    patterns: &Patterns,
+    out: &mut Output,
+    path: Option<&Path>,
+    reader: Box<dyn BufRead + '_>,
+    format_parts: Option<&[FormatPart]>,
+    capture_regexes: &[Regex],
+) -> Result<Searched> {
+    // like search_content, one line at a time as it is read: the lines before a match are kept
+    //   for before, the after context is printed as the next lines arrive
+    //   the number of lines is not known, so the gutter (of a file over limit_memory) doesn't align the line numbers
+    let file_name = display_name(path);
+    let mut lines: usize = 0;
+    let mut matched_lines: usize = 0;
+    let mut scanned_lines: usize = 0;
+    let mut found_patterns: Vec<String> = vec![];
+    let mut matched_indexes: Vec<usize> = vec![];
+    let mut counted_keys: HashMap<String, usize> = HashMap::new();
+    let mut long_lines: usize = 0;
+    let mut pattern_counts: Vec<usize> = vec![0; patterns.searches.len()];
+    // the last lines that were not printed, with their number and byte offset, for before
+    let mut previous: std::collections::VecDeque<(usize, usize, String)> = std::collections::VecDeque::new();
+    // how many of the next lines are still the after context of a match
+    let mut after_left: usize = 0;
+    // the number of the last printed line, with window a -- splits the blocks that don't touch
+    let mut last_printed: Option<usize> = None;
+    // the byte where the line starts, for byte_offset
+    let mut offset: usize = 0;
+    let prefix_of = |line_number: usize, is_match: bool, start: usize| {
+        let offset = if args.byte_offset { format!("{}{}", start, field_separator(args, is_match)) } else { String::new() };
+        format!("{}{}", line_prefix(line_number, is_match, args, 0), offset)
+    };
+
+    for (index, bytes) in reader.split(b'\n').enumerate() {
+        if past_line_range(args, index+1) {
+            break;
+        }
+        let mut line = decode(bytes.context("could not read the input")?, args.text).context("could not read the input")?;
+        if index == 0 && without_bom(args, &line).len() < line.len() {
+            line.drain(..'\u{feff}'.len_utf8());
+        }
+        if let Cow::Owned(stripped) = without_ansi(args, &line) {
+            line = stripped;
+        }
+        let start = offset;
+        offset += line.len() + 1;
+        if line.ends_with('\r') {
+            line.pop();
+        }
+        lines += 1;
+        if too_long(args, &line) {
+            long_lines += 1;
+        }
+
+        let found = match !in_line_range(args, index+1) || skipped_line(args, &line) {
+            true => None,
+            false => searched_text(args, path, index+1, &line).and_then(|text| {
+                scanned_lines += 1;
+                select(patterns.find(&text), args.invert).map(|indexes| (indexes, text.into_owned()))
+            }),
+        };
+        let Some((indexes, text)) = found else {
+            // a line that is not a match is after context, or kept in case the next one is
+            if after_left > 0 {
+                after_left -= 1;
+                if !ignored_blank(args, &line) {
+                    out.line(format_args!("{}{}", prefix_of(index+1, false, start), line));
+                }
+                last_printed = Some(index+1);
+            } else if args.before > 0 {
+                previous.push_back((index+1, start, line));
+                if previous.len() > args.before {
+                    previous.pop_front();
+                }
+            }
+            continue;
+        };
+        // with json_field the value is what is printed
+        let line = text;
+
+        matched_lines += 1;
+        if args.heatmap {
+            matched_indexes.push(index);
+        }
+        if args.sort_matches {
+            found_patterns.extend(indexes.iter().filter(|(start, end)| start < end).map(|&(start, end)| line[start..end].to_string()));
+            continue;
+        }
+        if let Some(re) = &args.count_by_regex {
+            count_keys(re, &line, &mut counted_keys);
+            continue;
+        }
+        if matched_lines == 1 {
+            print_heading(args, out, path);
+        }
+        if args.count {
+            if args.per_pattern {
+                for (count, matcher) in pattern_counts.iter_mut().zip(&patterns.searches) {
+                    if matcher.is_match(&line).unwrap_or_else(|error| exit_with(&error, &matcher.options().pattern)) {
+                        *count += 1;
+                    }
+                }
+            }
+            continue;
+        }
+        let selected = Selected { line_number: index+1, line: &line, indexes: &indexes, prefix: prefix_of(index+1, true, start) };
+        let Some(colored_line) = print_match(args, out, &selected, &file_name, format_parts, capture_regexes) else {
+            continue;
+        };
+        let block_start = previous.front().map_or(index+1, |(line_number, _, _)| *line_number);
+        if args.window.is_some() && last_printed.is_some_and(|last| block_start > last + 1) {
+            out.line(format_args!("--"));
+        }
+        for (line_number, start, before_line) in previous.drain(..) {
+            if !ignored_blank(args, &before_line) {
+                out.line(format_args!("{}{}", prefix_of(line_number, false, start), before_line));
+            }
+        }
+        out.line(format_args!("{}", colored_line));
+        after_left = args.after;
+        last_printed = Some(index+1);
+    }
+
+    print_count(args, out, path, matched_lines, &pattern_counts);
+
+    Ok(Searched { matched_lines, lines, scanned_lines, found_patterns, matched_indexes, counted_keys, long_lines })
+}
+
+/// The numbers of a searched input, added up over all the inputs for the summaries.
+struct Searched {
+    matched_lines: usize,
+    lines: usize,
+    scanned_lines: usize,
+    // the found patterns, collected only for sort_matches
+    found_patterns: Vec<String>,
+    // the indexes of the matching lines, collected only for heatmap
+    matched_indexes: Vec<usize>,
+    // the times each key was found, collected only for count_by
+    counted_keys: HashMap<String, usize>,
+    // the lines skipped by max_line_length
+    long_lines: usize,
+}
+
+fn search_content(
+    args: &SearchArgs,
+    patterns: &Patterns,
+    out: &mut Output,
+    path: Option<&Path>,
+    content: &str,
+    format_parts: Option<&[FormatPart]>,
+    capture_regexes: &[Regex],
+) -> Searched {
+    // print the selected lines of one input (path None is stdin) the way the options ask
+
+    // the section behaviour could print multiple time the same section, this avoid this behavior.
+    let mut sections_to_print: Vec<usize> = vec![];
+    // init a found_rows to keep colored lines to be printed with found pattern, a tuple of index and string
+    let mut found_rows: Vec<(usize, String)> = vec![];
+
+    // the lines are split once, every lookup by index is then checked with get()
+    let lines: Vec<&str> = split_records(content, args);
+
+    // with the gutter line numbers are right aligned to the widest one
+    let number_width = lines.len().to_string().len();
+    // with byte_offset the byte where each line (or record) starts in the input
+    let delimiter = args.record_delimiter.as_deref().unwrap_or("\n");
+    let line_starts: Vec<usize> = match args.byte_offset {
+        true => std::iter::once(0).chain(content.match_indices(delimiter).map(|(start, _)| start + delimiter.len())).collect(),
+        false => vec![],
+    };
+    let prefix_of = |line_number: usize, is_match: bool| {
+        let offset = line_starts.get(line_number - 1).map_or(String::new(), |start| format!("{}{}", start, field_separator(args, is_match)));
+        format!("{}{}", line_prefix(line_number, is_match, args, number_width), offset)
+    };
+
+    // the lines before printed_end are printed, the after context of the last match ends at after_end
+    let mut printed_end: usize = 0;
+    let mut after_end: usize = 0;
+    let print_context = |out: &mut Output, indexes: std::ops::Range<usize>| {
+        for i in indexes {
+            if let Some(line) = lines.get(i).filter(|line| !ignored_blank(args, line)) {
+                out.line(format_args!("{}{}", prefix_of(i+1, false), line));
+            }
+        }
+    };
+
+    // number of lines where the pattern is found, for the summary
+    let mut matched_lines: usize = 0;
+    // number of lines checked against the patterns, for scanned
+    let mut scanned_lines: usize = 0;
+    // number of lines skipped by max_line_length, for scanned
+    let long_lines = lines.iter().filter(|line| too_long(args, line)).count();
+    // the text of every found pattern, for sort_matches
+    let mut found_patterns: Vec<String> = vec![];
+    // the index of every matching line, for heatmap
+    let mut matched_indexes: Vec<usize> = vec![];
+    // the times each key of count_by was found
+    let mut counted_keys: HashMap<String, usize> = HashMap::new();
+    // the matching lines of each pattern, for per_pattern
+    let mut pattern_counts: Vec<usize> = vec![0; patterns.searches.len()];
+
+    // {file} in the format template
+    let file_name = display_name(path);
+
+    // Plain, a single pattern and no other mode: the library finds the matching lines and hands
+    //   them to the closure that prints them
+    if let [matcher] = &patterns.searches[..] {
+        if args.plain {
+            let printed = mygrep::for_each_match(content, matcher.options(), |found| {
+                matched_lines += 1;
+                if matched_lines == 1 {
+                    print_heading(args, out, path);
+                }
+                let selected = Selected { line_number: found.line_number, line: &found.line, indexes: &found.spans, prefix: prefix_of(found.line_number, true) };
+                if let Some(colored_line) = print_match(args, out, &selected, &file_name, format_parts, capture_regexes) {
+                    out.line(format_args!("{}", colored_line));
+                }
+            });
+            if let Err(error) = printed {
+                exit_with(&error, &matcher.options().pattern);
+            }
+            return Searched { matched_lines, lines: lines.len(), scanned_lines: lines.len(), found_patterns, matched_indexes, counted_keys, long_lines };
+        }
+    }
+
+    // Min count, an input with fewer matching lines is left out as if none had matched
+    if let Some(min_count) = args.min_count {
+        let in_range = || lines.iter().enumerate()
+            .filter(|&(index, line)| in_line_range(args, index+1) && !skipped_line(args, line));
+        let found = in_range()
+            .filter_map(|(_, line)| json_text(args, line).ok().flatten())
+            .filter(|text| patterns.is_match(text) != args.invert)
+            .count();
+        if (found as u64) < min_count {
+            return Searched { matched_lines: 0, lines: lines.len(), scanned_lines: in_range().count(), found_patterns: vec![], matched_indexes: vec![], counted_keys: HashMap::new(), long_lines };
+        }
+    }
+
+    // Near, print the lines from one pattern to the other when they are at most N lines apart
+    if let Some(distance) = args.near {
+        // the last line where each of the two patterns was found
+        let mut last_found: [Option<usize>; 2] = [None, None];
+        let mut windows_to_print: Vec<usize> = vec![];
+        for (index, &line) in lines.iter().enumerate() {
+            if past_line_range(args, index+1) {
+                break;
+            }
+            if !in_line_range(args, index+1) || skipped_line(args, line) {
+                continue;
+            }
+            scanned_lines += 1;
+            for pattern in 0..2 {
+                let searches = &patterns.searches[pattern..=pattern];
+                if find_patterns(line, searches, false).is_none() || patterns.excluded(line) {
+                    continue;
+                }
+                if let Some(other) = last_found[1 - pattern] {
+                    if index - other <= distance {
+                        windows_to_print.extend(other..=index);
+                    }
+                }
+                last_found[pattern] = Some(index);
+            }
+        }
+        // windows can overlap, like sections
+        windows_to_print.sort();
+        windows_to_print.dedup();
+        for index in windows_to_print {
+            let Some(&line) = lines.get(index).filter(|line| !ignored_blank(args, line)) else {
+                continue;
+            };
+            match patterns.find(line) {
+                Some(indexes) => {
+                    matched_lines += 1;
+                    if matched_lines == 1 {
+                        print_heading(args, out, path);
+                    }
+                    out.line(format_args!("{}{}", prefix_of(index+1, true), bracket(&highlight(line, &indexes, args), args)));
+                }
+                None => out.line(format_args!("{}{}", prefix_of(index+1, false), line)),
+            }
+        }
+        return Searched { matched_lines, lines: lines.len(), scanned_lines, found_patterns: vec![], matched_indexes: vec![], counted_keys: HashMap::new(), long_lines };
+    }
+
+    // Join lines, every N lines are searched and printed as a single one, numbered by the first
+    if let Some(size) = args.join_lines {
+        for (chunk, window) in lines.chunks(size as usize).enumerate() {
+            let first = chunk * size as usize;
+            if past_line_range(args, first+1) {
+                break;
+            }
+            if !in_line_range(args, first+1) {
+                continue;
+            }
+            scanned_lines += 1;
+            let joined = window.concat();
+            let Some(indexes) = select(patterns.find(&joined), args.invert) else {
+                continue;
+            };
+            matched_lines += 1;
+            if matched_lines == 1 {
+                print_heading(args, out, path);
+            }
+            if !args.count {
+                out.line(format_args!("{}{}", prefix_of(first+1, true), bracket(&highlight(&joined, &indexes, args), args)));
+            }
+        }
+        print_count(args, out, path, matched_lines, &[]);
+        return Searched { matched_lines, lines: lines.len(), scanned_lines, found_patterns: vec![], matched_indexes: vec![], counted_keys: HashMap::new(), long_lines };
+    }
+
+    // Diff, only the added lines are searched, numbered in the new file by the hunk headers
+    if args.diff {
+        // the file of the new side, and the lines of the hunk still to come on each side
+        let mut diff_file = String::new();
+        let mut file_matched = false;
+        let (mut new_number, mut old_left, mut new_left) = (0, 0, 0);
+        for &line in &lines {
+            if old_left == 0 && new_left == 0 {
+                // between the hunks, only the headers matter
+                if let Some(name) = line.strip_prefix("+++ ") {
+                    diff_file = diff_file_name(name);
+                    file_matched = false;
+                } else if let Some((start, old_count, new_count)) = parse_hunk_header(line) {
+                    (new_number, old_left, new_left) = (start, old_count, new_count);
+                }
+                continue;
+            }
+            let (kind, text) = line.split_at(line.len().min(1));
+            match kind {
+                "+" => new_left = new_left.saturating_sub(1),
+                "-" => {
+                    old_left = old_left.saturating_sub(1);
+                    continue;
+                }
+                // `\ No newline at end of file`
+                "\\" => continue,
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                    new_number += 1;
+                    continue;
+                }
+            }
+            new_number += 1;
+            if skipped_line(args, text) {
+                continue;
+            }
+            scanned_lines += 1;
+            let Some(indexes) = select(patterns.find(text), args.invert) else {
+                continue;
+            };
+            matched_lines += 1;
+            if args.count {
+                continue;
+            }
+            if !file_matched {
+                file_matched = true;
+                print_heading(args, out, Some(Path::new(&diff_file)));
+            }
+            let name = match args.heading {
+                true => String::new(),
+                false => format!("{}{}", diff_file.color(format!("{:?}", args.filename_color)), args.field_separator.as_deref().unwrap_or(":")),
+            };
+            let prefix = format!("{}{}", name, line_prefix(new_number - 1, true, args, 0));
+            let selected = Selected { line_number: new_number - 1, line: text, indexes: &indexes, prefix };
+            if let Some(colored_line) = print_match(args, out, &selected, &diff_file, format_parts, capture_regexes) {
+                out.line(format_args!("{}", colored_line));
+            }
+        }
+        print_count(args, out, path, matched_lines, &[]);
+        return Searched { matched_lines, lines: lines.len(), scanned_lines, found_patterns: vec![], matched_indexes: vec![], counted_keys: HashMap::new(), long_lines };
+    }
+
+    // Last, only the last matching line is kept, the search starts from the end of the file
+    //   (lines.len() when nothing matches, so no line is kept)
+    let last_match = args.last.then(|| {
+        (0..lines.len()).rev()
+            .filter(|&index| in_line_range(args, index+1) && !skipped_line(args, lines[index]))
+            .inspect(|_| scanned_lines += 1)
+            .find(|&index| select(patterns.find(lines[index]), args.invert).is_some())
+            .unwrap_or(lines.len())
+    });
+
+    for (index, &line) in lines.iter().enumerate() {
+
+        // past the end of args.lines nothing can be found anymore
+        if past_line_range(args, index+1) {
+            break;
+        }
+        if !in_line_range(args, index+1) || skipped_line(args, line) {
+            continue;
+        }
+        // with json_field the value is searched and printed instead of the line
+        let Some(text) = searched_text(args, path, index+1, line) else {
+            continue;
+        };
+        let line: &str = &text;
+        // with last the lines were scanned from the end already
+        match last_match {
+            Some(last) if index != last => continue,
+            Some(_) => {}
+            None => scanned_lines += 1,
+        }
+        // the line number is always index+1, the position in the file, also when inverting
+        let Some(indexes) = select(patterns.find(line), args.invert) else {
+            continue;
+        };
+
+        matched_lines += 1;
+        if args.heatmap {
+            matched_indexes.push(index);
+        }
+        // Sort matches, the found patterns are printed sorted after all the inputs
+        if args.sort_matches {
+            found_patterns.extend(indexes.iter().filter(|(start, end)| start < end).map(|&(start, end)| line[start..end].to_string()));
+            continue;
+        }
+        // Count by, the keys are counted and printed in a table after all the inputs
+        if let Some(re) = &args.count_by_regex {
+            count_keys(re, line, &mut counted_keys);
+            continue;
+        }
+        if matched_lines == 1 {
+            print_heading(args, out, path);
+        }
+        // Count, only the number of matching lines is printed, at the end
+        if args.count {
+            if args.per_pattern {
+                for (count, matcher) in pattern_counts.iter_mut().zip(&patterns.searches) {
+                    if matcher.is_match(line).unwrap_or_else(|error| exit_with(&error, &matcher.options().pattern)) {
+                        *count += 1;
+                    }
+                }
+            }
+            continue;
+        }
+        let selected = Selected { line_number: index+1, line, indexes: &indexes, prefix: prefix_of(index+1, true) };
+        let Some(colored_line) = print_match(args, out, &selected, &file_name, format_parts, capture_regexes) else {
+            continue;
+        };
+
+        // Dedupe context, the context and the match go to sections_to_print like a section,
+        //   so the lines shared by close matches are printed once
+        if args.dedupe_context {
+            let first = index.saturating_sub(args.before);
+            let last = (index + args.after).min(lines.len() - 1);
+            sections_to_print.extend((first..=last).filter(|&i| i == index || !ignored_blank(args, lines[i])));
+            found_rows.push((index, colored_line));
+            continue;
+        }
+
+        // Before lines, args.before is the number of lines to print before the match, default 0
+        //   the after lines of the previous match come first, up to this one, and a line already
+        //   printed as its context is not printed again
+        let first = index.saturating_sub(args.before).max(printed_end);
+        print_context(out, printed_end..after_end.min(first));
+        print_context(out, first..index);
+
+        if !args.section && !args.paragraph {
+            out.line(format_args!("{}", colored_line));
+        }
+
+        // After lines, args.after is the number of lines to print after the match, default 0
+        //   they are printed with the next match (or at the end), in case one of them is a match
+        printed_end = index + 1;
+        after_end = index + 1 + args.after;
+
+        // Section, if true print the section.
+        //   Section is the same indentation or more of the line where the pattern is found.
+        //   Section can start before the finding
+        if args.section {
+            // init indentation
+            let starting_indentation = indentation(line, args.tabs_c);
+            let mut section_indexes: Vec<usize> = vec![index];
+            // keep the index where pattern is found along with colored string
+            found_rows.push((index, colored_line));
+
+            // scroll backwards indexes, break if indentation is <= starting_indentation
+            for i in (0..index).rev() {
+                let Some(tmp_line) = lines.get(i) else {
+                    continue;
+                };
+                if ignored_blank(args, tmp_line) {
+                    continue;
+                }
+                let tmp_indentation = indentation(tmp_line, args.tabs_c);
+                if tmp_indentation < starting_indentation {
+                    // if indentation is <= starting_indentation, break
+                    //   get the index as head of the section
+                    section_indexes.push(i);
+                    break;
+                }
+                section_indexes.push(i);
+            }
+
+            // scroll forward indexes, break if indentation is < starting_indentation
+            for i in index..lines.len() {
+                let Some(tmp_line) = lines.get(i) else {
+                    break;
+                };
+                if ignored_blank(args, tmp_line) {
+                    continue;
+                }
+                let tmp_indentation = indentation(tmp_line, args.tabs_c);
+                if tmp_indentation < starting_indentation {
+                    // if indentation is < starting_indentation, break
+                    break;
+                }
+                section_indexes.push(i);
+            }
+
+            // Section context, keep only the lines at most N away from the match,
+            //   the head of the section included, never the lines outside of it
+            if let Some(context) = args.section_context {
+                section_indexes.retain(|&i| i.abs_diff(index) <= context);
+            }
+            sections_to_print.extend(section_indexes);
+
+        } else if args.paragraph {
+            // Paragraph, like section but the paragraph is delimited by the blank lines around the match,
+            //   the indexes go to sections_to_print too, so overlapping paragraphs are printed once
+            let is_blank = |i: &usize| lines.get(*i).is_some_and(|line| line.trim().is_empty());
+            let first = (0..index).rev().find(is_blank).map_or(0, |blank| blank + 1);
+            let last = (index..lines.len()).find(is_blank).unwrap_or(lines.len());
+            found_rows.push((index, colored_line));
+            sections_to_print.extend(first..last);
+        }
+    }
+
+    print_context(out, printed_end..after_end.min(lines.len()));
+
+    if args.section || args.paragraph || args.dedupe_context {
+        // sections_to_print is a list olf indexes to print, but are unordered and maybe duplicate.
+        //   we need to sort and remove duplicates
+        sections_to_print.sort();
+        sections_to_print.dedup();
+
+        // print all indexes in sections_to_print, with window a -- splits the blocks that don't touch
+        let mut previous: Option<usize> = None;
+        for index in sections_to_print {
+            if args.window.is_some() && previous.is_some_and(|previous| index > previous + 1) {
+                out.line(format_args!("--"));
+            }
+            previous = Some(index);
+            // if index in found_rows, print the colored line instead of the normal line
+            if let Some((_, colored_line)) = found_rows.iter().find(|x| x.0 == index) {
+                out.line(format_args!("{}", colored_line));
+                continue;
+            }
+
+            let Some(line) = lines.get(index) else {
+                continue;
+            };
+            out.line(format_args!("{}{}", prefix_of(index+1, false), line));
+        }
+    }
+
+    print_count(args, out, path, matched_lines, &pattern_counts);
+
+    Searched { matched_lines, lines: lines.len(), scanned_lines, found_patterns, matched_indexes, counted_keys, long_lines }
+}
+
+fn exit_with_command(args: &SearchArgs, command_output: &Option<(Vec<u8>, process::ExitStatus)>) {
+    // a failed command is the outcome, whatever was found in its output (1 if it has no code)
+    let Some((_, status)) = command_output else {
+        return;
+    };
+    if !status.success() {
+        warn(args, format_args!("the command failed, {}", status));
+        process::exit(status.code().unwrap_or(1));
+    }
+}
+
+fn sgr_color(code: u8) -> Option<Colors> {
+    // the color of an SGR foreground code, 30-37 and the bright 91-97
+    let colors = [Colors::Black, Colors::Red, Colors::Green, Colors::Yellow, Colors::Blue, Colors::Magenta, Colors::Cyan, Colors::White];
+    let bright = [Colors::BrightRed, Colors::BrightGreen, Colors::BrightYellow, Colors::BrightBlue, Colors::BrightMagenta, Colors::BrightCyan, Colors::BrightWhite];
+    match code {
+        30..=37 => Some(colors[(code - 30) as usize]),
+        91..=97 => Some(bright[(code - 91) as usize]),
+        _ => None,
+    }
+}
+
+fn apply_env_colors(args: &mut SearchArgs, matches: &ArgMatches) {
+    // MYGREP_COLORS like GREP_COLORS, `ms=01;31:fn=35:ln=32`: ms (or mt) is the match, fn the file
+    //   names and ln the line numbers, the other keys of grep are accepted and ignored
+    //   an option given on the command line wins, a wrong entry is reported and left out
+    let Ok(spec) = std::env::var("MYGREP_COLORS") else {
+        return;
+    };
+    let from_env = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+    for entry in spec.split(':').filter(|entry| !entry.is_empty()) {
+        let Some((key, value)) = entry.split_once('=') else {
+            if !["rv", "ne"].contains(&entry) {
+                warn(args, format_args!("MYGREP_COLORS: `{}` is not key=value, it is ignored", entry));
+            }
+            continue;
+        };
+        let codes: Option<Vec<u8>> = value.split(';').filter(|code| !code.is_empty()).map(|code| code.parse().ok()).collect();
+        let Some(codes) = codes else {
+            warn(args, format_args!("MYGREP_COLORS: `{}` is not a list of numbers, it is ignored", entry));
+            continue;
+        };
+        let color = codes.iter().find_map(|&code| sgr_color(code));
+        if let Some(&code) = codes.iter().find(|&&code| sgr_color(code).is_none() && ![0, 1, 3, 4, 9, 22].contains(&code)) {
+            warn(args, format_args!("MYGREP_COLORS: the code {} of `{}` is not supported, it is ignored", code, entry));
+        }
+        match key {
+            "ms" | "mt" => {
+                if let Some(color) = color.filter(|_| from_env("color")) {
+                    args.color = color;
+                }
+                if from_env("bold") {
+                    args.bold = codes.contains(&1);
+                }
+                args.italic |= codes.contains(&3) && from_env("italic");
+                args.underline |= codes.contains(&4) && from_env("underline");
+                args.strike |= codes.contains(&9) && from_env("strike");
+            }
+            "fn" => {
+                if let Some(color) = color.filter(|_| from_env("filename_color")) {
+                    args.filename_color = color;
+                }
+            }
+            "ln" => {
+                if from_env("line_number_color") {
+                    args.line_number_color = color.or(args.line_number_color);
+                }
+            }
+            "mc" | "sl" | "cx" | "bn" | "se" => {}
+            _ => warn(args, format_args!("MYGREP_COLORS: unknown key `{}`, it is ignored", key)),
+        }
+    }
+}
+
+/// Writes a generated script or page to stdout, a reader that stops early (like `head`) is not an error.
+fn write_generated(bytes: &[u8], what: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|_| stdout.flush()) {
+        Err(error) if error.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        written => written.with_context(|| format!("could not write {}", what)),
+    }
+}
+
+fn main() -> Result<()> {
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|error| error.exit());
+    // the search options are the ones of the search command, or given without it
+    let search_matches = matches.subcommand_matches("search").unwrap_or(&matches);
+    let mut args = match cli.command {
+        Some(Commands::Search(args)) => *args,
+        Some(Commands::Config) => {
+            print_config();
+            return Ok(());
+        }
+        Some(Commands::Completions { shell }) => {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "mygrep", &mut script);
+            return write_generated(&script, "the completions");
+        }
+        Some(Commands::Man) => {
+            // the man page is generated from the same definition as --help, so it is always in sync
+            let mut page = Vec::new();
+            clap_mangen::Man::new(Cli::command().name("mygrep"))
+                .render(&mut page)
+                .context("could not render the man page")?;
+            return write_generated(&page, "the man page");
+        }
+        Some(Commands::Bench { lines, runs }) => return bench(lines, runs),
+        // no command, clap already required the search arguments
+        None => cli.search.expect("search arguments are required without a command"),
+    };
+    apply_env_colors(&mut args, search_matches);
+    args.plain = plain_search(search_matches);
+
+    // the no_ flags turn off a default (like MYGREP_SMART_CASE or pretty), the last of a pair wins
+    // a diff is read for the numbers of its lines in the new file
+    if args.diff {
+        args.line_numbers = true;
+    }
+    // pretty is a preset, the flags it turns on are added to the given ones
+    if args.pretty {
+        args.heading = true;
+        args.line_numbers = true;
+    }
+    if args.no_bold {
+        args.bold = false;
+    }
+    if args.no_line_numbers {
+        args.line_numbers = false;
+    }
+    if args.no_heading {
+        args.heading = false;
+    }
+    if args.no_insensitive {
+        args.insensitive = false;
+    }
+    if args.no_smart_case {
+        args.smart_case = false;
+    }
+    if args.no_section {
+        args.section = false;
+    }
+
+    // with interactive the patterns come from stdin, a single positional is the path
+    if args.interactive && args.path.is_none() {
+        args.path = args.pattern.take().map(PathBuf::from);
+    }
+    // with -e or patterns_env the patterns are all options, so the first positional is the path
+    if args.patterns.is_empty() && args.patterns_env.is_none() {
+        args.patterns.extend(args.pattern.take());
+    } else if let Some(path) = args.pattern.take() {
+        if args.path.is_some() {
+            anyhow::bail!("only one path can be searched, the patterns are given with -e");
+        }
+        args.path = Some(PathBuf::from(path));
+    }
+    // the patterns of the variable come after the -e ones, empty lines are skipped
+    if let Some(var) = &args.patterns_env {
+        let value = std::env::var(var).with_context(|| format!("could not read the patterns from `{}`", var))?;
+        let before = args.patterns.len();
+        args.patterns.extend(value.lines().filter(|line| !line.is_empty()).map(str::to_string));
+        if args.patterns.len() == before {
+            anyhow::bail!("no pattern in the environment variable `{}`", var);
+        }
+    }
+
+    // the regex engine picks the regex mode, fancy is pcre and checked like it
+    match args.regex_engine {
+        Some(RegexEngine::Default) => {
+            if args.pcre {
+                anyhow::bail!("the default regex engine can't be used with pcre, use --regex-engine fancy");
+            }
+            args.regex = true;
+        }
+        Some(RegexEngine::Fancy) => {
+            if args.overlapping || args.replace.is_some() {
+                anyhow::bail!("the fancy regex engine can't be used with overlapping or replace");
+            }
+            args.pcre = true;
+        }
+        None => {}
+    }
+
+    // the legacy windows console shows the escape codes as text unless virtual terminal
+    //   processing is enabled, if it can't be the colors are turned off
+    #[cfg(windows)]
+    if colored::control::set_virtual_terminal(true).is_err() {
+        colored::control::set_override(false);
+    }
+
+    // a file gets no colors, unless asked for
+    if args.output.is_some() {
+        colored::control::set_override(false);
+    }
+
+    // pretty keeps the colors when piped
+    if args.pretty {
+        colored::control::set_override(true);
+    }
+
+    match args.color_mode {
+        Some(ColorMode::Always) => colored::control::set_override(true),
+        Some(ColorMode::Never) => colored::control::set_override(false),
+        Some(ColorMode::Auto) | None => {}
+    }
+
+    // ascii wins over everything, no escape codes at all, and html has its own styles
+    if args.ascii || args.html {
+        colored::control::set_override(false);
+    }
+
+    // the escapes are turned into the chars they stand for before compiling the patterns
+    if args.interpret_escapes {
+        for pattern in args.patterns.iter_mut().chain(args.and_not.iter_mut()) {
+            *pattern = interpret_escapes(pattern).map_err(anyhow::Error::msg)?;
+        }
+    }
+
+    // pre, post, record_delimiter and field_separator are not patterns, their escapes are always interpreted
+    for text in args.pre.iter_mut().chain(args.post.iter_mut()).chain(args.record_delimiter.iter_mut()).chain(args.field_separator.iter_mut()) {
+        *text = interpret_escapes(text).map_err(anyhow::Error::msg)?;
+    }
+
+    // print all args for debug, to stderr and without colors so piped matches stay clean
+    if args.debug {
+        eprintln!("----------------------------");
+        eprintln!("args.pattern:      {:?}", args.pattern);
+        eprintln!("args.patterns:     {:?}", args.patterns);
+        eprintln!("args.patterns_env: {:?}", args.patterns_env);
+        eprintln!("args.all:          {}", args.all);
+        eprintln!("args.and_not:      {:?}", args.and_not);
+        eprintln!("args.lines:        {:?}", args.lines);
+        eprintln!("args.since:        {:?}", args.since);
+        eprintln!("args.until:        {:?}", args.until);
+        eprintln!("args.time_format:  {:?}", args.time_format);
+        eprintln!("args.keep_untimed: {}", args.keep_untimed);
+        eprintln!("args.json_field:   {:?}", args.json_field);
+        eprintln!("args.near:         {:?}", args.near);
+        eprintln!("args.join_lines:   {:?}", args.join_lines);
+        eprintln!("args.diff:         {}", args.diff);
+        eprintln!("args.path:         {:?}", args.path);
+        eprintln!("args.color:        {:?}", args.color);
+        eprintln!("args.rainbow:      {}", args.rainbow);
+        eprintln!("args.line_number_color: {:?}", args.line_number_color);
+        eprintln!("args.filename_color: {:?}", args.filename_color);
+        eprintln!("args.color_line:   {:?}", args.color_line);
+        eprintln!("args.first_match_only: {}", args.first_match_only);
+        eprintln!("args.max_matches_per_line: {:?}", args.max_matches_per_line);
+        eprintln!("args.min_matches_per_line: {:?}", args.min_matches_per_line);
+        eprintln!("args.color_mode:   {:?}", args.color_mode);
+        eprintln!("args.output:       {:?}", args.output);
+        eprintln!("args.bold:         {}", args.bold);
+        eprintln!("args.underline:    {}", args.underline);
+        eprintln!("args.italic:       {}", args.italic);
+        eprintln!("args.strike:       {}", args.strike);
+        eprintln!("args.line_numbers: {}", args.line_numbers);
+        eprintln!("args.line_buffered: {}", args.line_buffered);
+        eprintln!("args.wrap:         {}", args.wrap);
+        eprintln!("args.snippet:      {:?}", args.snippet);
+        eprintln!("args.html:         {}", args.html);
+        eprintln!("args.limit_memory: {:?}", args.limit_memory);
+        eprintln!("args.max_line_length: {:?}", args.max_line_length);
+        eprintln!("args.max_output_bytes: {:?}", args.max_output_bytes);
+        eprintln!("args.column:       {}", args.column);
+        eprintln!("args.column_visual: {}", args.column_visual);
+        eprintln!("args.command:      {:?}", args.command);
+        eprintln!("args.archive:      {}", args.archive);
+        eprintln!("args.path_contains: {:?}", args.path_contains);
+        eprintln!("args.files_from:   {:?}", args.files_from);
+        eprintln!("args.files_from0:  {:?}", args.files_from0);
+        eprintln!("args.interactive:  {}", args.interactive);
+        eprintln!("args.heading:      {}", args.heading);
+        eprintln!("args.record_delimiter: {:?}", args.record_delimiter);
+        eprintln!("args.null:         {}", args.null);
+        eprintln!("args.pretty:       {}", args.pretty);
+        eprintln!("args.ascii:        {}", args.ascii);
+        eprintln!("args.gutter:       {}", args.gutter);
+        eprintln!("args.context_indent: {}", args.context_indent);
+        eprintln!("args.field_separator: {:?}", args.field_separator);
+        eprintln!("args.regex:        {}", args.regex);
+        eprintln!("args.pcre:         {}", args.pcre);
+        eprintln!("args.regex_engine: {:?}", args.regex_engine);
+        eprintln!("args.interpret_escapes: {}", args.interpret_escapes);
+        eprintln!("args.replace:      {:?}", args.replace);
+        eprintln!("args.output_dir:   {:?}", args.output_dir);
+        eprintln!("args.overwrite:    {}", args.overwrite);
+        eprintln!("args.extract:      {:?}", args.extract);
+        eprintln!("args.numeric_range: {}", args.numeric_range);
+        eprintln!("args.fuzzy:        {:?}", args.fuzzy);
+        eprintln!("args.invert:       {}", args.invert);
+        eprintln!("args.overlapping:  {}", args.overlapping);
+        eprintln!("args.word:         {}", args.word);
+        eprintln!("args.word_ascii:   {}", args.word_ascii);
+        eprintln!("args.insensitive:  {}", args.insensitive);
+        eprintln!("args.smart_case:   {}", args.smart_case);
+        eprintln!("args.after:        {}", args.after);
+        eprintln!("args.before:       {}", args.before);
+        eprintln!("args.context:      {:?}", args.context);
+        eprintln!("args.dedupe_context: {}", args.dedupe_context);
+        eprintln!("args.window:       {:?}", args.window);
+        eprintln!("args.text:         {}", args.text);
+        eprintln!("args.keep_bom:     {}", args.keep_bom);
+        eprintln!("args.strip_ansi:   {}", args.strip_ansi);
+        eprintln!("args.byte_offset:  {}", args.byte_offset);
+        eprintln!("args.only_matching: {}", args.only_matching);
+        eprintln!("args.pre:          {:?}", args.pre);
+        eprintln!("args.post:         {:?}", args.post);
+        eprintln!("args.join:         {:?}", args.join);
+        eprintln!("args.files_with_matches: {}", args.files_with_matches);
+        eprintln!("args.quiet:        {}", args.quiet);
+        eprintln!("args.fail_on_match: {}", args.fail_on_match);
+        eprintln!("args.last:         {}", args.last);
+        eprintln!("args.sort_matches: {}", args.sort_matches);
+        eprintln!("args.count_by:     {:?}", args.count_by);
+        eprintln!("args.per_pattern:  {}", args.per_pattern);
+        eprintln!("args.min_count:    {:?}", args.min_count);
+        eprintln!("args.count:        {}", args.count);
+        eprintln!("args.include_zero: {}", args.include_zero);
+        eprintln!("args.offsets_only: {}", args.offsets_only);
+        eprintln!("args.format:       {:?}", args.format);
+        eprintln!("args.section:      {}", args.section);
+        eprintln!("args.ignore_blank: {}", args.ignore_blank);
+        eprintln!("args.paragraph:    {}", args.paragraph);
+        eprintln!("args.section_context: {:?}", args.section_context);
+        eprintln!("args.filename_match: {}", args.filename_match);
+        eprintln!("args.summary:      {}", args.summary);
+        eprintln!("args.summary_by_file: {}", args.summary_by_file);
+        eprintln!("args.heatmap:      {}", args.heatmap);
+        eprintln!("args.no_messages:  {}", args.no_messages);
+        eprintln!("args.scanned:      {}", args.scanned);
+        eprintln!("args.count_files:  {}", args.count_files);
+        eprintln!("args.count_files_matched: {}", args.count_files_matched);
+        eprintln!("args.debug:        {}", args.debug);
+        eprintln!("----------------------------");
+        eprintln!();
+    }
+
+    // one search for each pattern, compiled before reading the input
+    let patterns = compile_patterns(&args);
+
+    // the results go to stdout, or to the output file
+    let mut out = Output::new(&args)?;
+
+    // Filename match, the pattern is looked for in the paths, the contents are not read
+    if args.filename_match {
+        let root = args.path.clone().unwrap_or_else(|| PathBuf::from("."));
+        let mut paths = walk(&root, !args.no_messages);
+        paths.retain(|path| path_selected(&args, path));
+        let mut files_matched: usize = 0;
+        for path in &paths {
+            let name = path.display().to_string();
+            if let Some(indexes) = select(patterns.find(&name), args.invert) {
+                files_matched += 1;
+                out.file_name(format_args!("{}", highlight(&name, &indexes, &args)), args.null);
+            }
+        }
+        print_files_searched(&args, paths.len());
+        print_files_matched(&args, files_matched);
+        return out.flush();
+    }
+
+    // since and until are read once, in the format of the timestamps
+    if args.since.is_some() || args.until.is_some() {
+        let parse = |time: &Option<String>, name: &str| match time {
+            Some(time) => parse_time(time, &args.time_format)
+                .map(Some)
+                .with_context(|| format!("{} `{}` is not a time in the format `{}` or a date", name, time, args.time_format)),
+            None => Ok(None),
+        };
+        let since = parse(&args.since, "since")?.unwrap_or(NaiveDateTime::MIN);
+        let until = parse(&args.until, "until")?.unwrap_or(NaiveDateTime::MAX);
+        args.time_window = Some((since, until));
+    }
+
+    // count_by is compiled once, an invalid regex is an error like the pattern
+    if let Some(count_by) = &args.count_by {
+        match Regex::new(count_by) {
+            Ok(re) => args.count_by_regex = Some(re),
+            Err(error) => exit_with(&error.into(), count_by),
+        }
+    }
+
+    // window is before and after at once, with the context of close matches merged
+    if let Some((before, after)) = args.window {
+        args.before = before;
+        args.after = after;
+        args.dedupe_context = true;
+    }
+
+    // context fills the sides that were not given, like grep -C with -A or -B
+    if let Some(n) = args.context {
+        if args.after == 0 {
+            args.after = n;
+        }
+        if args.before == 0 {
+            args.before = n;
+        }
+    }
+
+    // section_context is a section mode, only shorter
+    if args.section_context.is_some() {
+        args.section = true;
+    }
+
+    // If section is true and before and after are != 0, print warning and set after and before to 0
+    if args.section && (args.before != 0 || args.after != 0) {
+        if !args.no_messages {
+            eprintln!("Section is not compatible with after and or before. Ignoring After and or Before.");
+        }
+        args.after = 0;
+        args.before = 0;
+    }
+
+    // with invert the context lines are the matching ones around a selected line, printed once
+    //   in order even where the context of two selected lines meets, like the stream does
+    if args.invert && (args.after > 0 || args.before > 0) {
+        args.dedupe_context = true;
+    }
+
+    // the format template is parsed once, an unknown placeholder is an error before any output
+    let format_parts = match &args.format {
+        Some(template) => Some(parse_format(template).map_err(anyhow::Error::msg)?),
+        None => None,
+    };
+    if args.near.is_some() && patterns.searches.len() != 2 {
+        anyhow::bail!("near needs exactly two patterns, given with -e");
+    }
+    let capture_regexes = if args.replace.is_some() || args.extract.is_some() { compile_capture_regexes(&args) } else { vec![] };
+    // every group of extract must be in every pattern, or its column would always be empty
+    if let Some(names) = &args.extract {
+        for re in &capture_regexes {
+            if let Some(name) = names.iter().find(|name| !re.capture_names().any(|group| group == Some(name.as_str()))) {
+                anyhow::bail!("the pattern `{}` has no group named `{}` to extract", re.as_str(), name);
+            }
+        }
+    }
+
+    // with command its output is searched in place of stdin, once it is done
+    let command_output = match &args.command {
+        Some(command) => Some(run_command(command)?),
+        None => None,
+    };
+
+    // Interactive, the file is read once and searched again for every pattern read from stdin
+    if args.interactive {
+        let Some(path) = args.path.clone() else {
+            anyhow::bail!("interactive needs a file to search, stdin is where the patterns are read");
+        };
+        let content = read_input(Some(&path), args.text)?;
+        let mut pattern = args.patterns.pop();
+        loop {
+            if pattern.is_none() {
+                eprint!("{}", "pattern> ".bold());
+                io::stderr().flush().context("could not write the prompt")?;
+                let mut answer = String::new();
+                io::stdin().lock().read_line(&mut answer).context("could not read the pattern")?;
+                pattern = Some(answer.trim_end_matches(['\n', '\r']).to_string()).filter(|answer| !answer.is_empty());
+                if pattern.is_none() {
+                    break;
+                }
+            }
+            args.patterns = pattern.take().into_iter().collect();
+            // an invalid regex ends this search, not the session
+            let searches = match search_options(&args, &args.patterns).iter().map(Matcher::new).collect::<Result<Vec<Matcher>, MyGrepError>>() {
+                Ok(searches) => searches,
+                Err(error) => {
+                    eprintln!("{}", error.to_string().color("red").bold());
+                    continue;
+                }
+            };
+            let patterns = Patterns {
+                searches,
+                exclusions: compile(search_options(&args, &args.and_not)),
+                all: args.all,
+                min_matches: args.min_matches_per_line.map_or(1, |min| min as usize),
+            };
+            let searched = search_content(&args, &patterns, &mut out, Some(&path), &without_ansi(&args, without_bom(&args, &content)), format_parts.as_deref(), &capture_regexes);
+            print_summary(&args, searched.matched_lines, usize::from(searched.matched_lines > 0), searched.lines);
+            out.flush()?;
+        }
+        return Ok(());
+    }
+
+    // Output dir, the replaced files are written in a mirrored tree instead of printed
+    if let Some(output_dir) = &args.output_dir {
+        let root = args.path.clone().expect("output_dir requires a path");
+        write_replaced(&args, &mut out, &patterns, &capture_regexes, &root, output_dir)?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    // the inputs: the files listed by files_from, or the path (a directory is walked), or stdin
+    let list = args.files_from.as_ref().or(args.files_from0.as_ref());
+    let walked = list.is_none() && command_output.is_none() && args.path.as_deref().is_some_and(Path::is_dir);
+    let listed = list.is_some() || walked;
+    let inputs: Vec<Option<PathBuf>> = match (list, &args.path) {
+        (Some(_), Some(_)) => anyhow::bail!("the files to search are listed by files_from, no path can be given"),
+        (None, Some(_)) if command_output.is_some() => anyhow::bail!("the output of the command is searched, no path can be given"),
+        (Some(list), None) => read_file_list(list, args.files_from0.is_some())?.into_iter().map(Some).collect(),
+        (None, Some(path)) if walked => {
+            walk(path, !args.no_messages).into_iter().map(Some).collect()
+        }
+        (None, Some(path)) => vec![Some(path.clone())],
+        (None, None) => vec![None],
+    };
+    // path_contains drops the files before they are opened, stdin is always searched
+    let inputs: Vec<Option<PathBuf>> = inputs.into_iter()
+        .filter(|input| input.as_deref().is_none_or(|path| path_selected(&args, path)))
+        .collect();
+    // the results of the listed files (and of the members of an archive) are told apart by
+    //   their heading (count names them itself)
+    args.several_inputs = listed || args.archive;
+    if args.several_inputs && !args.count {
+        args.heading = true;
+    }
+
+    // Files with matches and quiet, the outcome is known at the first match so the rest is not read
+    //   quiet stops at the first file that matches
+    if args.files_with_matches || args.quiet {
+        let mut scanned_lines: usize = 0;
+        let mut searched: usize = 0;
+        let mut files_matched: usize = 0;
+        for input in &inputs {
+            searched += 1;
+            // a listed file that can't be read is skipped, the others are still searched
+            let reader = match (&command_output, input) {
+                (Some((stdout, _)), None) => Ok(Box::new(stdout.as_slice()) as Box<dyn BufRead>),
+                _ => open_input(input.as_deref()),
+            };
+            let found = match reader.and_then(|reader| first_match(reader, input.as_deref(), &patterns, &args, &mut scanned_lines)) {
+                Ok(found) => found,
+                Err(error) if listed => {
+                    warn(&args, format_args!("{:#}", error));
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            if !found {
+                continue;
+            }
+            files_matched += 1;
+            if args.quiet {
+                break;
+            }
+            out.file_name(format_args!("{}", display_name(input.as_deref())), args.null);
+        }
+        // the search of a file stops at its first match, so each file counts as one match
+        print_summary(&args, files_matched, files_matched, scanned_lines);
+        print_scanned(&args, scanned_lines, 0);
+        print_files_searched(&args, searched);
+        print_files_matched(&args, files_matched);
+        out.flush()?;
+        exit_with_command(&args, &command_output);
+        // quiet answers only with the exit code, 0 when the pattern is found and 1 when it isn't,
+        //   the other way around with fail_on_match
+        if args.fail_on_match {
+            if files_matched > 0 {
+                process::exit(1);
+            }
+        } else if args.quiet && files_matched == 0 {
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // Html, the lines go in a <pre> of a page of their own
+    if args.html {
+        let title = html_escape(&args.patterns.join(" "));
+        out.line(format_args!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>mygrep {}</title>\n</head>\n<body>\n<pre>", title));
+    }
+
+    // the numbers of all the inputs, for the summaries
+    let mut matched_lines: usize = 0;
+    let mut total_lines: usize = 0;
+    let mut scanned_lines: usize = 0;
+    let mut files_matched: usize = 0;
+    let mut counts: Vec<(String, usize)> = vec![];
+    let mut found_patterns: Vec<String> = vec![];
+    let mut counted_keys: HashMap<String, usize> = HashMap::new();
+    let mut long_lines: usize = 0;
+    let mut searched_files: usize = 0;
+    for input in &inputs {
+        // a fifo and stdin are searched as they are read when the mode allows it, so the matches
+        //   of a producer that never ends are printed as they arrive
+        //   a file over limit_memory is read like them, without the modes that need all of it
+        let over_limit = input.as_deref().is_some_and(|path| over_memory_limit(&args, path));
+        if over_limit && !streamable(&args) {
+            warn(&args, format_args!(
+                "{} is bigger than limit_memory, it is searched line by line without {}",
+                display_name(input.as_deref()),
+                whole_input_modes(&args).join(", "),
+            ));
+        }
+        let read = if command_output.is_none() && (over_limit || streamable(&args) && is_stream(input.as_deref())) {
+            open_input(input.as_deref())
+                .and_then(|reader| stream_input(&args, &patterns, &mut out, input.as_deref(), reader, format_parts.as_deref(), &capture_regexes))
+                .map(|searched| vec![(input.clone(), searched)])
+        } else {
+            // with archive a zip is searched member by member, `archive.zip:member` is their name
+            match input {
+                None if command_output.is_some() => {
+                    let (stdout, _) = command_output.as_ref().expect("the command was run");
+                    decode(stdout.clone(), args.text)
+                        .context("could not read the output of the command")
+                        .map(|content| vec![(None, content)])
+                }
+                Some(path) if args.archive && is_zip(path) => read_archive(path, args.text),
+                _ => read_input(input.as_deref(), args.text).map(|content| vec![(input.clone(), content)]),
+            }.map(|contents| contents.into_iter().map(|(name, content)| {
+                let searched = search_content(&args, &patterns, &mut out, name.as_deref(), &without_ansi(&args, without_bom(&args, &content)), format_parts.as_deref(), &capture_regexes);
+                (name, searched)
+            }).collect())
+        };
+        let searched_inputs: Vec<(Option<PathBuf>, Searched)> = match read {
+            Ok(searched_inputs) => searched_inputs,
+            Err(error) if listed => {
+                warn(&args, format_args!("{:#}", error));
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        for (name, searched) in searched_inputs {
+            searched_files += 1;
+            matched_lines += searched.matched_lines;
+            total_lines += searched.lines;
+            scanned_lines += searched.scanned_lines;
+            long_lines += searched.long_lines;
+            if searched.matched_lines > 0 {
+                files_matched += 1;
+            }
+            counts.push((display_name(name.as_deref()), searched.matched_lines));
+            found_patterns.extend(searched.found_patterns);
+            for (key, count) in searched.counted_keys {
+                *counted_keys.entry(key).or_insert(0) += count;
+            }
+            print_heatmap(&args, name.as_deref(), searched.lines, &searched.matched_indexes);
+        }
+    }
+    if args.sort_matches {
+        print_sorted_matches(&args, &mut out, found_patterns);
+    }
+    if args.count_by.is_some() {
+        print_counted_keys(&args, &mut out, counted_keys);
+    }
+    if args.html {
+        out.line(format_args!("</pre>\n</body>\n</html>"));
+    }
+
+    print_regex_hint(&args, matched_lines);
+    print_summary(&args, matched_lines, files_matched, total_lines);
+    print_scanned(&args, scanned_lines, long_lines);
+    print_files_searched(&args, searched_files);
+    print_files_matched(&args, files_matched);
+    print_summary_by_file(&args, &counts);
+
+    out.flush()?;
+    exit_with_command(&args, &command_output);
+    if args.fail_on_match && matched_lines > 0 {
+        process::exit(1);
+    }
     Ok(())
 }
@@ -0,0 +1,494 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn mygrep(args: &[&str], stdin: &str) -> Output {
+    mygrep_with_env(args, &[], stdin)
+}
+
+fn mygrep_with_env(args: &[&str], env: &[(&str, &str)], stdin: &str) -> Output {
+    // run the built binary with `stdin` as its input, colors are off since the output is piped
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mygrep"))
+        .args(args)
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("mygrep runs");
+    child.stdin.take().expect("stdin is piped").write_all(stdin.as_bytes()).expect("stdin is written");
+    child.wait_with_output().expect("mygrep ends")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).expect("the output is UTF-8")
+}
+
+#[test]
+fn insensitive_literal_spans_are_in_the_original_line() {
+    // lowercasing these changes their length in bytes, the matches are still sliced right
+    let output = mygrep(&["-I", "foo", "-o"], "KELVIN \u{212a} foo\n");
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "foo\n");
+    assert_eq!(stdout(&mygrep(&["-I", "foo", "-o"], "İİİ foo bar\n")), "foo\n");
+    assert_eq!(stdout(&mygrep(&["--smart-case", "foo", "-o"], "ẞ foo\n")), "foo\n");
+    assert_eq!(stdout(&mygrep(&["-I", "k", "-o"], "\u{212a}\n")), "\u{212a}\n");
+}
+
+fn fixture_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    // a fresh directory of the temp dir with these files, the subdirectories are created
+    let dir = std::env::temp_dir().join(format!("mygrep-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, content) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().expect("a file is in a directory")).expect("the directory is created");
+        std::fs::write(path, content).expect("the file is written");
+    }
+    dir
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).expect("the errors are UTF-8")
+}
+
+#[test]
+fn summary_of_a_directory_search() {
+    let dir = fixture_dir("summary", &[("a.txt", "foo\nbar\n"), ("sub/b.txt", "foo\n"), ("c.txt", "bar\n")]);
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    let output = mygrep(&["foo", dir_arg, "--files-with-matches", "--summary"], "");
+    assert_eq!(stderr(&output), "2 matches in 2 files (3 lines scanned)\n");
+    let output = mygrep(&["zzz", dir_arg, "--files-with-matches", "--summary"], "");
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "0 matches in 0 files (4 lines scanned)\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn summary_by_file_is_rejected_with_files_with_matches() {
+    for first_match_mode in ["--files-with-matches", "--quiet"] {
+        let output = mygrep(&["foo", ".", first_match_mode, "--summary-by-file"], "");
+        assert_eq!(output.status.code(), Some(2));
+        assert!(stderr(&output).contains("cannot be used with"));
+    }
+}
+
+#[test]
+fn path_contains_filters_a_directory_search() {
+    let dir = fixture_dir("path-contains", &[("src/a.rs", "TODO one\nx\n"), ("doc/b.md", "TODO doc\n")]);
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    let output = mygrep(&["TODO", dir_arg, "--path-contains", "src/"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("{}\nTODO one\n", dir.join("src/a.rs").display()));
+    let output = mygrep(&["TODO", dir_arg, "--count"], "");
+    assert_eq!(stdout(&output), format!("{}: 1\n{}: 1\n", dir.join("doc/b.md").display(), dir.join("src/a.rs").display()));
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn output_dir_mirrors_the_replaced_files() {
+    let dir = fixture_dir("output-dir", &[("src/a.rs", "let foo = 1;\r\nbar\nfoo()"), ("src/b.rs", "none\n"), ("src/sub/c.rs", "foo foo\n")]);
+    let (src, out) = (dir.join("src"), dir.join("out"));
+    let args = ["-R", "foo", src.to_str().expect("the path is UTF-8"), "-r", "baz", "--output-dir", out.to_str().expect("the path is UTF-8")];
+    let output = mygrep(&args, "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("{}\n{}\n", out.join("a.rs").display(), out.join("sub/c.rs").display()));
+    // the line terminators are kept, a file without a match is not written, the originals are untouched
+    assert_eq!(std::fs::read_to_string(out.join("a.rs")).expect("a.rs is written"), "let baz = 1;\r\nbar\nbaz()");
+    assert_eq!(std::fs::read_to_string(out.join("sub/c.rs")).expect("c.rs is written"), "baz baz\n");
+    assert!(!out.join("b.rs").exists());
+    assert_eq!(std::fs::read_to_string(src.join("sub/c.rs")).expect("c.rs is there"), "foo foo\n");
+
+    // an existing file is skipped and reported, overwrite replaces it
+    let mut args = args.to_vec();
+    args[4] = "qux";
+    let output = mygrep(&args, "");
+    assert!(stderr(&output).contains("exists, it is not replaced without overwrite"));
+    assert_eq!(std::fs::read_to_string(out.join("sub/c.rs")).expect("c.rs is written"), "baz baz\n");
+    args.push("--overwrite");
+    mygrep(&args, "");
+    assert_eq!(std::fs::read_to_string(out.join("sub/c.rs")).expect("c.rs is written"), "qux qux\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn count_of_several_files_leaves_out_the_zeros() {
+    let dir = fixture_dir("include-zero", &[("a.txt", "foo\nfoo\n"), ("b.txt", "bar\n")]);
+    let (a, b) = (dir.join("a.txt").display().to_string(), dir.join("b.txt").display().to_string());
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    assert_eq!(stdout(&mygrep(&["foo", dir_arg, "--count"], "")), format!("{}: 2\n", a));
+    assert_eq!(stdout(&mygrep(&["foo", dir_arg, "--count", "--include-zero"], "")), format!("{}: 2\n{}: 0\n", a, b));
+    let list = format!("{}\n{}\n", b, a);
+    assert_eq!(stdout(&mygrep(&["foo", "--files-from", "-", "--count"], &list)), format!("{}: 2\n", a));
+    assert_eq!(stdout(&mygrep(&["foo", "--files-from", "-", "--count", "--include-zero"], &list)), format!("{}: 0\n{}: 2\n", b, a));
+    // a single input always has its count
+    assert_eq!(stdout(&mygrep(&["foo", &b, "--count"], "")), "0\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn overlapping_patterns_are_styled_once() {
+    // foo and oba share `o`, the line gets a single colored `fooba`
+    let output = mygrep(&["-e", "foo", "-e", "oba", "--color-mode", "always"], "foobar\n");
+    assert_eq!(stdout(&output), "\u{1b}[1;31mfooba\u{1b}[0mr\n");
+}
+
+#[test]
+fn min_matches_per_line_counts_the_matches() {
+    assert_eq!(stdout(&mygrep(&["a", "--min-matches-per-line", "2"], "a a\na\nbab a\n")), "a a\nbab a\n");
+    // the default of one match doesn't drop the lines a zero-width regex selects
+    assert_eq!(stdout(&mygrep(&["-R", "^"], "abc\n\n")), "abc\n\n");
+}
+
+#[test]
+fn zero_width_matches_select_the_line_and_highlight_nothing() {
+    for pattern in ["^", "$", r"\b"] {
+        let output = mygrep(&["-R", pattern, "--color-mode", "always"], "x y\n");
+        assert_eq!(stdout(&output), "x y\n", "{}", pattern);
+        let output = mygrep(&["-R", pattern, "-o"], "x y\n");
+        assert!(output.status.success(), "{}", pattern);
+        assert_eq!(stdout(&output), "", "{}", pattern);
+        assert_eq!(stdout(&mygrep(&["-R", pattern, "--count"], "x y\nz\n")), "2\n", "{}", pattern);
+    }
+}
+
+#[test]
+fn word_boundaries_of_literals_and_regexes_agree() {
+    // the letters of any script are word characters, with word_ascii only the ASCII ones
+    let input = "café\nnaïve\n字foo\nfoo\n";
+    for regex in [&[][..], &["-R"][..]] {
+        let search = |pattern: &str, ascii: bool| {
+            let mut args = regex.to_vec();
+            args.extend([pattern, "--word"]);
+            if ascii {
+                args.push("--word-ascii");
+            }
+            stdout(&mygrep(&args, input))
+        };
+        assert_eq!(search("foo", false), "foo\n");
+        assert_eq!(search("caf", false), "");
+        assert_eq!(search("na", false), "");
+        assert_eq!(search("ve", false), "");
+        assert_eq!(search("foo", true), "字foo\nfoo\n");
+        assert_eq!(search("caf", true), "café\n");
+        assert_eq!(search("na", true), "naïve\n");
+        assert_eq!(search("ve", true), "naïve\n");
+    }
+}
+
+#[test]
+fn invert_with_context_from_a_file_and_from_stdin() {
+    // the selected lines are the ones without x, the context lines around them are printed once
+    let input = "a\nx\nb\nx\nx\n";
+    let dir = fixture_dir("invert-context", &[("ax.txt", input)]);
+    let file = dir.join("ax.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    for (context, expected) in [("-A", "1: a\n2: x\n3: b\n4: x\n"), ("-B", "1: a\n2: x\n3: b\n")] {
+        assert_eq!(stdout(&mygrep(&["-v", context, "1", "x", file, "-l"], "")), expected, "{} file", context);
+        assert_eq!(stdout(&mygrep(&["-v", context, "1", "x", "-l"], input)), expected, "{} stdin", context);
+    }
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn files_with_matches_reads_only_up_to_the_first_match() {
+    // the bytes after the match are not UTF-8, reading them would be an error
+    let mut content = "head\nfoo\n".to_string().into_bytes();
+    for _ in 0..100_000 {
+        content.extend_from_slice(b"\xff\xfe not text\n");
+    }
+    let dir = fixture_dir("prefix-read", &[]);
+    std::fs::create_dir_all(&dir).expect("the directory is created");
+    let file = dir.join("large.log");
+    std::fs::write(&file, content).expect("the file is written");
+    let file = file.to_str().expect("the path is UTF-8");
+    let output = mygrep(&["foo", file, "--files-with-matches", "--scanned"], "");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), format!("{}\n", file));
+    assert_eq!(stderr(&output), "Scanned 2 lines\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn quiet_over_a_directory() {
+    let dir = fixture_dir("quiet", &[("a.txt", "bar\nfoo\n"), ("sub/b.txt", "x\nx\nx\n"), ("sub/c.txt", "foo\n")]);
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    // found in the first file, the rest of the walk is never read
+    let output = mygrep(&["foo", dir_arg, "-q", "--scanned"], "");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "Scanned 2 lines\n");
+    let output = mygrep(&["x", dir_arg, "-q"], "");
+    assert_eq!(output.status.code(), Some(0));
+    let output = mygrep(&["zzz", dir_arg, "-q"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn context_stops_at_the_file_boundaries() {
+    // the context of each match would reach into the other file, it stops at its own start and end
+    let dir = fixture_dir("context-files", &[("f1.txt", "a1\nfoo\na3\n"), ("f2.txt", "foo\nb2\nb3\n")]);
+    let (f1, f2) = (dir.join("f1.txt").display().to_string(), dir.join("f2.txt").display().to_string());
+    let expected = format!("{}\na1\nfoo\na3\n--\n{}\nfoo\nb2\nb3\n", f1, f2);
+    let output = mygrep(&["foo", "--files-from", "-", "-C", "2"], &format!("{}\n{}\n", f1, f2));
+    assert_eq!(stdout(&output), expected);
+    assert_eq!(stdout(&mygrep(&["foo", dir.to_str().expect("the path is UTF-8"), "-C", "2"], "")), expected);
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn a_pipe_is_searched_as_it_is_written() {
+    // the first match is printed while the producer is still writing, before any EOF
+    use std::io::{BufRead, BufReader};
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mygrep"))
+        .arg("foo")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("mygrep runs");
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let reader = std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            sender.send(line.expect("the output is UTF-8")).expect("the test is waiting");
+        }
+    });
+    stdin.write_all(b"bar\nfoo 1\n").expect("stdin is written");
+    stdin.flush().expect("stdin is flushed");
+    let first = receiver.recv_timeout(std::time::Duration::from_secs(10));
+    stdin.write_all(b"baz\nfoo 2\n").expect("stdin is written");
+    drop(stdin);
+    assert_eq!(first.as_deref(), Ok("foo 1"));
+    assert_eq!(receiver.recv_timeout(std::time::Duration::from_secs(10)).as_deref(), Ok("foo 2"));
+    assert!(child.wait().expect("mygrep ends").success());
+    reader.join().expect("the output is read");
+}
+
+#[test]
+fn the_bom_of_a_file_is_stripped() {
+    let dir = fixture_dir("bom", &[("bom.csv", "\u{feff}name,x\nfoo\n")]);
+    let file = dir.join("bom.csv");
+    let file = file.to_str().expect("the path is UTF-8");
+    assert_eq!(stdout(&mygrep(&["-R", "^name", file], "")), "name,x\n");
+    assert_eq!(stdout(&mygrep(&["-R", "^name", file, "--keep-bom"], "")), "");
+    assert_eq!(stdout(&mygrep(&["n", file, "--keep-bom"], "")), "\u{feff}name,x\n");
+    assert_eq!(mygrep(&["-R", "^name", file, "-q"], "").status.code(), Some(0));
+    assert_eq!(stdout(&mygrep(&["-R", "^name"], "\u{feff}name\n")), "name\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn the_help_has_a_short_line_per_option_and_the_details_in_long_help() {
+    let short = stdout(&mygrep(&["-h"], ""));
+    for line in short.lines().filter(|line| line.contains(" option is used ")) {
+        assert!(line.chars().count() <= 130, "{}", line);
+    }
+    assert!(!short.contains("sh -c"));
+    assert!(stdout(&mygrep(&["--help"], "")).contains("The command is run by the shell (sh -c, cmd /C on Windows)"));
+}
+
+fn mygrep_into_a_closed_pipe(args: &[&str]) -> Output {
+    // the reader is gone before mygrep writes anything, like `| head -1` once it has its line
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mygrep"))
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("mygrep runs");
+    drop(child.stdout.take());
+    child.wait_with_output().expect("mygrep ends")
+}
+
+#[test]
+fn completions_into_a_closed_pipe_exit_quietly() {
+    let output = mygrep_into_a_closed_pipe(&["completions", "bash"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+    assert!(stdout(&mygrep(&["completions", "bash"], "")).contains("mygrep"));
+}
+
+#[test]
+fn man_into_a_closed_pipe_exits_quietly() {
+    let output = mygrep_into_a_closed_pipe(&["man"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stderr(&output), "");
+    assert!(stdout(&mygrep(&["man"], "")).starts_with(".ie"));
+}
+
+#[test]
+fn the_no_flags_turn_off_the_parts_of_pretty() {
+    let dir = fixture_dir("pretty", &[("f.txt", "a\nfoo\n")]);
+    let file = dir.join("f.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    let pretty = |more: &[&str]| stdout(&mygrep(&[&["foo", file, "--pretty", "--color-mode", "never"], more].concat(), ""));
+    assert_eq!(pretty(&[]), format!("{}\n2: foo\n", file));
+    assert_eq!(pretty(&["--no-line-numbers"]), format!("{}\nfoo\n", file));
+    assert_eq!(pretty(&["--no-heading"]), "2: foo\n");
+    assert_eq!(pretty(&["--no-heading", "--heading", "--no-line-numbers"]), format!("{}\nfoo\n", file));
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn the_context_of_close_matches_is_printed_once() {
+    let content = "1\nfoo\n3\nfoo\n5\n6\n7\n8\nfoo\n10\n";
+    let dir = fixture_dir("close-context", &[("c.txt", content)]);
+    let file = dir.join("c.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    let from_file = stdout(&mygrep(&["foo", file, "-B2", "-A2", "-l"], ""));
+    assert_eq!(from_file, "1: 1\n2: foo\n3: 3\n4: foo\n5: 5\n6: 6\n7: 7\n8: 8\n9: foo\n10: 10\n");
+    assert_eq!(stdout(&mygrep(&["foo", "-B2", "-A2", "-l"], content)), from_file);
+    assert_eq!(stdout(&mygrep(&["foo", file, "-A1"], "")), "foo\n3\nfoo\n5\nfoo\n10\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn the_gutter_of_stdin_is_aligned() {
+    let content = "1\nfoo\n3\n4\n5\n6\n7\n8\n9\nfoo\n";
+    assert_eq!(stdout(&mygrep(&["foo", "-l", "--gutter", "--ascii"], content)), " 2 | foo\n10 | foo\n");
+}
+
+#[test]
+fn window_splits_the_blocks_that_dont_touch() {
+    let content = "1\nfoo\n3\n4\n5\n6\n7\nfoo\n9\nfoo\n11\n";
+    let dir = fixture_dir("window", &[("w.txt", content)]);
+    let file = dir.join("w.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    let expected = "1: 1\n2: foo\n3: 3\n--\n7: 7\n8: foo\n9: 9\n10: foo\n11: 11\n";
+    assert_eq!(stdout(&mygrep(&["foo", file, "--window", "1:1", "-l"], "")), expected);
+    assert_eq!(stdout(&mygrep(&["foo", "--window", "1:1", "-l"], content)), expected);
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn a_plain_search_prints_like_the_full_loop() {
+    // a plain search goes through for_each_match, max_output_bytes sends it through the loop of the modes
+    let dir = fixture_dir("plain", &[("a.txt", "foo\nbar\nfood\n"), ("b/c.txt", "xfoo\n")]);
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    for args in [&["foo", dir_arg][..], &["-R", "fo+", dir_arg, "-l"], &["-I", "FOO", dir_arg, "--heading", "-w"]] {
+        let plain = stdout(&mygrep(args, ""));
+        assert!(!plain.is_empty());
+        assert_eq!(stdout(&mygrep(&[args, &["--max-output-bytes", "1000000"]].concat(), "")), plain);
+    }
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn a_directory_is_searched_in_the_order_of_its_paths() {
+    // written out of order, the walk still gives them sorted by name
+    let dir = fixture_dir("sorted", &[("c.txt", "foo c\n"), ("a/z.txt", "foo az\n"), ("b.txt", "foo b\n"), ("a/b.txt", "foo ab\n"), ("d.txt", "bar\n")]);
+    let dir_arg = dir.to_str().expect("the path is UTF-8");
+    let name = |file: &str| dir.join(file).display().to_string();
+    assert_eq!(stdout(&mygrep(&["foo", dir_arg, "--files-with-matches"], "")), format!("{}\n{}\n{}\n{}\n", name("a/b.txt"), name("a/z.txt"), name("b.txt"), name("c.txt")));
+    let headed = format!("{}\nfoo ab\n{}\nfoo az\n{}\nfoo b\n{}\nfoo c\n", name("a/b.txt"), name("a/z.txt"), name("b.txt"), name("c.txt"));
+    assert_eq!(stdout(&mygrep(&["foo", dir_arg], "")), headed);
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn overlapping_is_rejected_with_a_regex() {
+    assert_eq!(stdout(&mygrep(&["--overlapping", "aa", "-o"], "aaaa\n")), "aa\naa\naa\n");
+    let output = mygrep(&["--overlapping", "-R", "a+"], "aaaa\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("cannot be used with"), "{}", stderr(&output));
+}
+
+#[test]
+fn interpret_escapes_of_a_literal() {
+    let escaped = |pattern: &str, stdin: &str| stdout(&mygrep(&["--interpret-escapes", pattern], stdin));
+    assert_eq!(escaped(r"a\tb", "a\tb\nab\na\\tb\n"), "a\tb\n");
+    assert_eq!(escaped(r"x\\y", "x\\y\nxy\n"), "x\\y\n");
+    assert_eq!(escaped(r"\x41", "A\nB\n"), "A\n");
+    // an unknown escape keeps its backslash
+    assert_eq!(escaped(r"x\qy", "x\\qy\nxqy\n"), "x\\qy\n");
+    // a line is never split by \n, a record can be
+    assert_eq!(stdout(&mygrep(&["--interpret-escapes", r"o\nb", "--record-delimiter", ";"], "foo\nbar;baz\n")), "foo\nbar\n");
+    let output = mygrep(&["--interpret-escapes", r"\xZ1"], "A\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("expected two hex digits"));
+}
+
+#[test]
+fn numeric_range_prints_the_numbers_in_it() {
+    assert_eq!(stdout(&mygrep(&["--numeric-range", "500-599", "-o"], "a 500\nb 600\nc -502 15000\n")), "500\n502\n");
+    assert_eq!(mygrep(&["--numeric-range", "599-500"], "500\n").status.code(), Some(2));
+}
+
+#[test]
+fn invert_keeps_the_line_numbers_of_the_file() {
+    let dir = fixture_dir("invert", &[("v.txt", "foo\nfoo\nbar\nfoo\nbaz\n")]);
+    let file = dir.join("v.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    assert_eq!(stdout(&mygrep(&["-v", "foo", file, "-l"], "")), "3: bar\n5: baz\n");
+    assert_eq!(stdout(&mygrep(&["-v", "foo", "-l"], "foo\nfoo\nbar\nfoo\nbaz\n")), "3: bar\n5: baz\n");
+    let output = mygrep(&["-v", "-o", "foo", file], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("cannot be used with"));
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
+
+#[test]
+fn record_delimiter_numbers_the_records() {
+    assert_eq!(stdout(&mygrep(&["foo", "--record-delimiter", r"\0", "-l"], "foo one\0bar\0two foo\0")), "1: foo one\n3: two foo\n");
+    // a record can hold newlines, it is printed whole
+    assert_eq!(stdout(&mygrep(&["foo", "--record-delimiter", r"\x1e", "-l"], "a\nfoo\x1eb\x1efoo c\n")), "1: a\nfoo\n3: foo c\n");
+    assert_eq!(stdout(&mygrep(&["^b", "-R", "--record-delimiter", ";;"], "a;;b;;ab")), "b\n");
+}
+
+#[test]
+fn diff_searches_the_added_lines_by_their_new_numbers() {
+    let diff = "diff --git a/x b/x\n--- a/x\n+++ b/x\n\
+        @@ -1,3 +1,4 @@\n keep TODO\n+new TODO one\n ctx\n-old TODO\n+other\n\
+        @@ -10,2 +11,3 @@ fn head\n ctx TODO\n+second TODO\n tail\n\
+        diff --git a/y.rs b/y.rs\n--- a/y.rs\n+++ b/y.rs\n@@ -5,0 +6,2 @@\n+TODO y1\n+fine\n";
+    // the context and the removed lines have a TODO too, they are not searched
+    assert_eq!(stdout(&mygrep(&["--diff", "TODO"], diff)), "x:2: new TODO one\nx:12: second TODO\ny.rs:6: TODO y1\n");
+    assert_eq!(stdout(&mygrep(&["--diff", "TODO", "--heading"], diff)), "x\n2: new TODO one\n12: second TODO\ny.rs\n6: TODO y1\n");
+}
+
+#[test]
+fn since_and_until_bound_the_timestamps() {
+    let log = "2024-05-01 09:59:59 ERROR early\n2024-05-01 10:00:00 ERROR start\n  at trace ERROR\n2024-05-01 10:30:00 ERROR end\n2024-05-01 10:30:01 ERROR late\n";
+    let window = ["ERROR", "--since", "2024-05-01 10:00:00", "--until", "2024-05-01 10:30:00"];
+    // both ends are included, the untimed line is skipped unless keep_untimed
+    assert_eq!(stdout(&mygrep(&window, log)), "2024-05-01 10:00:00 ERROR start\n2024-05-01 10:30:00 ERROR end\n");
+    assert_eq!(stdout(&mygrep(&[&window[..], &["--keep-untimed"]].concat(), log)), "2024-05-01 10:00:00 ERROR start\n  at trace ERROR\n2024-05-01 10:30:00 ERROR end\n");
+    assert_eq!(stdout(&mygrep(&["ERROR", "--since", "2024-05-01"], log)).lines().count(), 4);
+    let access = "[01/May/2024:10:00:00 +0000] GET 500\n[30/Apr/2024:23:00:00 +0000] GET 500\n";
+    assert_eq!(stdout(&mygrep(&["500", "--since", "2024-05-01", "--time-format", "[%d/%b/%Y:%H:%M:%S"], access)), "[01/May/2024:10:00:00 +0000] GET 500\n");
+    let output = mygrep(&["ERROR", "--since", "yesterday"], log);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("is not a time in the format"));
+}
+
+#[test]
+fn json_field_searches_the_value_at_a_path() {
+    let lines = "{\"request\":{\"status\":500,\"path\":\"/a\"}}\n{\"request\":{\"path\":\"/500\"}}\nnot json 500\n{\"request\":{\"status\":200}}\n";
+    // the line without the field and the one that is not JSON are skipped, the value is printed
+    let output = mygrep(&["500", "--json-field", "request.status", "-l"], lines);
+    assert_eq!(stdout(&output), "1: 500\n");
+    assert!(stderr(&output).starts_with("-: line 3 is not JSON, it is skipped"), "{}", stderr(&output));
+    assert_eq!(stderr(&mygrep(&["500", "--json-field", "request.status", "--no-messages"], lines)), "");
+    assert_eq!(stdout(&mygrep(&["/", "--json-field", "request.path"], lines)), "/a\n/500\n");
+}
+
+#[test]
+fn mygrep_colors_styles_the_matches_the_names_and_the_numbers() {
+    let dir = fixture_dir("colors", &[("f.txt", "a foo\n")]);
+    let file = dir.join("f.txt");
+    let file = file.to_str().expect("the path is UTF-8");
+    let colored = |env: &str, more: &[&str]| {
+        let output = mygrep_with_env(&[&["foo", file, "-l", "--heading", "--color-mode", "always"], more].concat(), &[("MYGREP_COLORS", env)], "");
+        (stdout(&output), stderr(&output))
+    };
+    let styled = |name: &str, number: &str, found: &str| format!("\x1b[{}m{}\x1b[0m\n\x1b[{}m1\x1b[0m: a \x1b[{}mfoo\x1b[0m\n", name, file, number, found);
+    assert_eq!(colored("ms=01;32:fn=34:ln=33", &[]), (styled("1;34", "33", "1;32"), String::new()));
+    // the options of the command line win
+    assert_eq!(colored("ms=01;32:fn=34:ln=33", &["--color", "blue", "--filename-color", "cyan"]), (styled("1;36", "33", "1;34"), String::new()));
+    // a wrong entry is reported and the defaults are kept
+    let (printed, warnings) = colored("ms=zz:xx=1", &[]);
+    assert_eq!(printed, styled("1;35", "2", "1;31"));
+    assert_eq!(warnings, "MYGREP_COLORS: `ms=zz` is not a list of numbers, it is ignored\nMYGREP_COLORS: unknown key `xx`, it is ignored\n");
+    std::fs::remove_dir_all(dir).expect("the fixture is removed");
+}
@@ -0,0 +1,210 @@
+use mygrep::{expand_replacement, for_each_match, get_fuzzy_span, matches, merge_spans, search_lines, MyGrepError, SearchOptions};
+
+#[test]
+fn insensitive_literal_spans_slice_the_line() {
+    let opts = SearchOptions { pattern: "foo".into(), insensitive: true, ..Default::default() };
+    for line in ["KELVIN \u{212a} FOO", "İİİ foo bar", "ẞ Foo"] {
+        let found = search_lines(line, &opts).expect("the pattern is valid");
+        let spans = &found[0].spans;
+        assert_eq!(spans.len(), 1);
+        assert!(found[0].line[spans[0].0..spans[0].1].eq_ignore_ascii_case("foo"));
+    }
+}
+
+#[test]
+fn insensitive_overlapping_literal() {
+    let opts = SearchOptions { pattern: "aa".into(), insensitive: true, overlapping: true, ..Default::default() };
+    let found = search_lines("AaAa", &opts).expect("the pattern is valid");
+    assert_eq!(found[0].spans, vec![(0, 2), (1, 3), (2, 4)]);
+}
+
+#[test]
+fn for_each_match_hands_the_matches_to_a_vec() {
+    let opts = SearchOptions { pattern: "lorem".into(), ..Default::default() };
+    let mut found = vec![];
+    let count = for_each_match("lorem ipsum\nnone\nsit lorem", &opts, |found_match| {
+        found.push((found_match.line_number, found_match.spans.clone()));
+    }).expect("the pattern is valid");
+    assert_eq!(count, 2);
+    assert_eq!(found, vec![(1, vec![(0, 5)]), (3, vec![(4, 9)])]);
+}
+
+#[test]
+fn for_each_match_hands_the_matches_to_a_channel() {
+    let opts = SearchOptions { pattern: "o+".into(), regex: true, ..Default::default() };
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let count = for_each_match("foo\nbar\nboo", &opts, |found_match| {
+        sender.send(found_match.line.clone()).expect("the receiver is there");
+    }).expect("the pattern is valid");
+    drop(sender);
+    assert_eq!(count, 2);
+    assert_eq!(receiver.iter().collect::<Vec<String>>(), vec!["foo", "boo"]);
+}
+
+#[test]
+fn for_each_match_with_an_invalid_pattern_calls_nothing() {
+    let opts = SearchOptions { pattern: "(".into(), regex: true, ..Default::default() };
+    let mut called = false;
+    assert!(for_each_match("(", &opts, |_| called = true).is_err());
+    assert!(!called);
+}
+
+#[test]
+fn merge_spans_unions_the_overlaps() {
+    // overlap and containment become one span, the order of the input doesn't matter
+    assert_eq!(merge_spans(&[(0, 3), (2, 5)]), vec![(0, 5)]);
+    assert_eq!(merge_spans(&[(0, 10), (2, 5)]), vec![(0, 10)]);
+    assert_eq!(merge_spans(&[(6, 8), (2, 5), (0, 3)]), vec![(0, 5), (6, 8)]);
+    assert_eq!(merge_spans(&[(4, 6), (0, 2), (1, 5)]), vec![(0, 6)]);
+}
+
+#[test]
+fn merge_spans_keeps_the_touching_spans_apart() {
+    assert_eq!(merge_spans(&[(3, 6), (0, 3)]), vec![(0, 3), (3, 6)]);
+}
+
+#[test]
+fn merge_spans_drops_the_empty_spans() {
+    assert_eq!(merge_spans(&[]), vec![]);
+    assert_eq!(merge_spans(&[(2, 2), (0, 0)]), vec![]);
+    assert_eq!(merge_spans(&[(1, 1), (0, 3), (3, 3)]), vec![(0, 3)]);
+}
+
+#[test]
+fn zero_width_regex_spans_are_empty() {
+    let spans = |pattern: &str, line: &str| {
+        let opts = SearchOptions { pattern: pattern.into(), regex: true, ..Default::default() };
+        search_lines(line, &opts).expect("the pattern is valid").remove(0).spans
+    };
+    assert_eq!(spans("^", "x y"), vec![(0, 0)]);
+    assert_eq!(spans("$", "x y"), vec![(3, 3)]);
+    assert_eq!(spans(r"\b", "x y"), vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    assert_eq!(spans("x*", "ab"), vec![(0, 0), (1, 1), (2, 2)]);
+}
+
+#[test]
+fn the_builder_rejects_the_options_that_cant_go_together() {
+    let refused = [
+        SearchOptions::builder("aa").overlapping(true).regex(true).build(),
+        SearchOptions::builder("1-9").numeric_range(true).regex(true).build(),
+        SearchOptions::builder("foo").fuzzy(1).regex(true).build(),
+        SearchOptions::builder("foo").word_ascii(true).build(),
+    ];
+    for built in refused {
+        assert!(matches!(built, Err(MyGrepError::InvalidOptions(_))), "{:?}", built);
+    }
+    assert!(matches!(SearchOptions::builder("9-1x").numeric_range(true).build(), Err(MyGrepError::InvalidRange(_))));
+}
+
+#[test]
+fn the_builder_sets_the_options() {
+    let opts = SearchOptions::builder("error").regex(true).insensitive(true).word(true).word_ascii(true).build().expect("the options go together");
+    assert_eq!(opts.pattern, "error");
+    assert!(opts.regex && opts.insensitive && opts.word && opts.word_ascii);
+    assert!(!opts.pcre && !opts.overlapping && !opts.numeric_range && opts.fuzzy.is_none());
+    let found = search_lines("an ERROR\nerrors", &opts).expect("the pattern is valid");
+    assert_eq!(found.iter().map(|found| found.line_number).collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+fn fuzzy_spans_of_the_edits() {
+    let pattern: Vec<char> = "connection".chars().collect();
+    let span = |line: &str, max_distance: usize| get_fuzzy_span(line, &pattern, max_distance, false);
+    // no edit, then one substitution, insertion or deletion
+    assert_eq!(span("a connection refused", 0), Some((2, 12)));
+    assert_eq!(span("a connektion refused", 0), None);
+    assert_eq!(span("a connektion refused", 1), Some((2, 12)));
+    assert_eq!(span("a connnection refused", 1), Some((2, 13)));
+    assert_eq!(span("a conection refused", 1), Some((2, 11)));
+    // two edits need a distance of 2
+    assert_eq!(span("a conektion refused", 1), None);
+    assert_eq!(span("a conektion refused", 2), Some((2, 11)));
+    // the fewest edits win over the leftmost
+    assert_eq!(span("conection connection", 1), Some((10, 20)));
+}
+
+#[test]
+fn fuzzy_insensitive_span() {
+    let pattern: Vec<char> = "error".chars().collect();
+    assert_eq!(get_fuzzy_span("an EROR", &pattern, 1, true), Some((3, 7)));
+    assert_eq!(get_fuzzy_span("an EROR", &pattern, 1, false), None);
+}
+
+#[test]
+fn matches_of_a_reader_are_numbered_by_line() {
+    let opts = SearchOptions { pattern: "foo".into(), ..Default::default() };
+    let found: Vec<_> = matches(std::io::Cursor::new("foo\nbar\nx foo\n"), &opts).collect::<Result<_, _>>().expect("the input is read");
+    assert_eq!(found.iter().map(|found| (found.line_number, found.line.as_str(), found.spans.clone())).collect::<Vec<_>>(), vec![(1, "foo", vec![(0, 3)]), (3, "x foo", vec![(2, 5)])]);
+}
+
+#[test]
+fn matches_yield_the_read_errors() {
+    let opts = SearchOptions { pattern: "foo".into(), ..Default::default() };
+    let found: Vec<_> = matches(std::io::Cursor::new(b"foo\n\xff\xfe\nfoo\n".to_vec()), &opts).collect();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].as_ref().map(|found| found.line_number).ok(), Some(1));
+    assert!(matches!(found[1], Err(MyGrepError::Encoding)));
+
+    // a reader that fails after its first line
+    struct Failing(bool);
+    impl std::io::Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if std::mem::replace(&mut self.0, true) {
+                return Err(std::io::Error::other("the disk is gone"));
+            }
+            buf[..4].copy_from_slice(b"foo\n");
+            Ok(4)
+        }
+    }
+    let found: Vec<_> = matches(std::io::BufReader::new(Failing(false)), &opts).collect();
+    assert_eq!(found.len(), 2);
+    assert!(found[0].is_ok());
+    assert!(matches!(&found[1], Err(MyGrepError::Io(error)) if error.to_string() == "the disk is gone"));
+}
+
+#[test]
+fn matches_of_an_invalid_regex_is_that_error() {
+    let opts = SearchOptions { pattern: "(".into(), regex: true, ..Default::default() };
+    let found: Vec<_> = matches(std::io::Cursor::new("(\n"), &opts).collect();
+    assert!(matches!(found[..], [Err(MyGrepError::InvalidRegex(_))]));
+}
+
+#[test]
+fn expand_replacement_of_the_groups_and_the_cases() {
+    let re = regex::Regex::new(r"(?<user>\w+)@(\w+)").expect("the regex is valid");
+    let caps = re.captures("Ann@Example").expect("the line matches");
+    assert_eq!(expand_replacement(&caps, "$2 ${user}"), "Example Ann");
+    assert_eq!(expand_replacement(&caps, "${2}x $user"), "Examplex Ann");
+    assert_eq!(expand_replacement(&caps, r"\U$1\E-\L$2\E-$1"), "ANN-example-Ann");
+    assert_eq!(expand_replacement(&caps, r"\U$1\L$2"), "ANNexample");
+    assert_eq!(expand_replacement(&caps, "$$1 costs $$"), "$1 costs $");
+    assert_eq!(expand_replacement(&caps, r"a\tb"), r"a\tb");
+}
+
+#[test]
+fn overlapping_literal() {
+    let opts = SearchOptions { pattern: "aa".into(), overlapping: true, ..Default::default() };
+    let found = search_lines("aaaa\nAaAa", &opts).expect("the pattern is valid");
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].spans, vec![(0, 2), (1, 3), (2, 4)]);
+}
+
+#[test]
+fn numeric_range_spans() {
+    let opts = SearchOptions { pattern: "500-599".into(), numeric_range: true, ..Default::default() };
+    let spans = |line: &str| search_lines(line, &opts).expect("the range is valid").first().map(|found| found.spans.clone());
+    // both bounds are in the range
+    assert_eq!(spans("GET / 500 0.1s"), Some(vec![(6, 9)]));
+    assert_eq!(spans("599 and 600 and 499"), Some(vec![(0, 3)]));
+    assert_eq!(spans("600 499"), None);
+    // a number is a whole run of digits, without its sign
+    assert_eq!(spans("15000 5000 1550"), None);
+    assert_eq!(spans("t=-502"), Some(vec![(3, 6)]));
+    assert_eq!(spans("0550"), Some(vec![(0, 4)]));
+    let brackets = SearchOptions { pattern: "[1-1]".into(), numeric_range: true, ..Default::default() };
+    assert_eq!(search_lines("a1 11 1", &brackets).expect("the range is valid")[0].spans, vec![(1, 2), (6, 7)]);
+    for invalid in ["9-1", "5", "a-9", "-1-5"] {
+        let opts = SearchOptions { pattern: invalid.into(), numeric_range: true, ..Default::default() };
+        assert!(matches!(search_lines("5", &opts), Err(MyGrepError::InvalidRange(_))), "{}", invalid);
+    }
+}